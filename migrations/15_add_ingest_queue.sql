@@ -0,0 +1,15 @@
+-- Migration to create the ingest_queue table, which stores every accepted
+-- webhook payload before it is acknowledged so drops survive restarts
+
+CREATE TABLE ingest_queue (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    payload TEXT NOT NULL,                           -- Raw payload_json as received
+    status VARCHAR(20) NOT NULL DEFAULT 'pending',   -- pending, processing, processed, rejected, failed
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    received_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    processed_at TIMESTAMP
+);
+
+-- Add index for the worker's status lookups
+CREATE INDEX idx_ingest_queue_status ON ingest_queue(status);
@@ -0,0 +1,30 @@
+-- Migration to track drop fingerprints so retried or double-reported drops
+-- are only credited once
+
+CREATE TABLE drop_fingerprints (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    fingerprint VARCHAR(64) NOT NULL,   -- SHA-256 hex of account, source and items
+    player VARCHAR(255) NOT NULL,
+    source VARCHAR(255) NOT NULL,
+    dropped_at INTEGER NOT NULL,        -- Unix timestamp of the drop
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_drop_fingerprints_fingerprint ON drop_fingerprints(fingerprint);
+
+-- Drops that were skipped as duplicates, kept for admin review
+CREATE TABLE duplicate_drops (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    fingerprint VARCHAR(64) NOT NULL,   -- SHA-256 hex, as in drop_fingerprints
+    original_id INTEGER,                -- drop_fingerprints row of the credited drop
+    player VARCHAR(255) NOT NULL,
+    source VARCHAR(255) NOT NULL,
+    items TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    dropped_at INTEGER NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (original_id) REFERENCES drop_fingerprints(id) ON DELETE SET NULL
+);
+
+CREATE INDEX idx_duplicate_drops_created_at ON duplicate_drops(created_at);
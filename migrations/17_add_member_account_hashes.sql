@@ -0,0 +1,10 @@
+-- Migration to bind team members to their Dink account hash, so drops keep
+-- resolving after a name change and can't be sent under another player's name
+
+-- Confirmed hash, set by an admin
+ALTER TABLE team_members ADD COLUMN account_hash VARCHAR(64);
+
+-- Hash seen on the member's first drop, waiting for an admin to confirm it
+ALTER TABLE team_members ADD COLUMN pending_account_hash VARCHAR(64);
+
+CREATE INDEX idx_team_members_account_hash ON team_members(account_hash);
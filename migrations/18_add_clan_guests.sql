@@ -0,0 +1,6 @@
+-- Migration for players allowed to take part without being in an event clan
+
+CREATE TABLE clan_guests (
+    username VARCHAR(50) PRIMARY KEY,
+    added_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
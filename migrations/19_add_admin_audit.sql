@@ -0,0 +1,14 @@
+-- Migration for the audit trail of admin actions, from Discord commands and the admin API
+
+CREATE TABLE admin_audit (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    source VARCHAR(16) NOT NULL,     -- 'discord' or 'api'
+    actor VARCHAR(100) NOT NULL,     -- Discord user or API caller address
+    action VARCHAR(50) NOT NULL,
+    target VARCHAR(100) NOT NULL,
+    details TEXT,
+    outcome TEXT NOT NULL,           -- 'ok' or the reason the action was refused
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_admin_audit_created_at ON admin_audit(created_at);
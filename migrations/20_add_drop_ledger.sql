@@ -0,0 +1,28 @@
+-- Migration for the ledger of credited items, one row per item of each credited drop
+
+CREATE TABLE drop_ledger (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    drop_id INTEGER,                    -- drop_fingerprints row shared by the items of one drop
+    player VARCHAR(255) NOT NULL,
+    team_id INTEGER NOT NULL,
+    source VARCHAR(255) NOT NULL,
+    item_name VARCHAR(255) NOT NULL,
+    category VARCHAR(100) NOT NULL,
+    raw_quantity INTEGER NOT NULL,      -- Quantity in the drop
+    credited_quantity INTEGER NOT NULL, -- Quantity added to the team's resources
+    multiplier REAL NOT NULL,
+    flat_bonus INTEGER NOT NULL,
+    handicap REAL NOT NULL,
+    global_multiplier REAL NOT NULL,
+    ingest_path VARCHAR(16) NOT NULL,   -- 'webhook' or 'channel'
+    message_id INTEGER,                 -- Discord message the drop was read from, for channel drops
+    dropped_at INTEGER NOT NULL,        -- Unix timestamp of the drop
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (drop_id) REFERENCES drop_fingerprints(id) ON DELETE SET NULL,
+    FOREIGN KEY (team_id) REFERENCES teams(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_drop_ledger_drop_id ON drop_ledger(drop_id);
+CREATE INDEX idx_drop_ledger_team_id ON drop_ledger(team_id);
+CREATE INDEX idx_drop_ledger_message_id ON drop_ledger(message_id);
@@ -0,0 +1,4 @@
+-- Migration to mark drop ledger entries that an admin or a deleted Dink message reverted
+
+ALTER TABLE drop_ledger ADD COLUMN reverted_at TIMESTAMP;
+ALTER TABLE drop_ledger ADD COLUMN reverted_by VARCHAR(100);
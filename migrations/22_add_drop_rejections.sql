@@ -0,0 +1,20 @@
+-- Migration for the log of rejected drops, with the reason and where the drop came from
+
+CREATE TABLE drop_rejections (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    player VARCHAR(255) NOT NULL,
+    team_name VARCHAR(255),             -- Team of the player, if they have one
+    source VARCHAR(255) NOT NULL,
+    reason_code VARCHAR(32) NOT NULL,   -- e.g. 'slayer_level', 'duplicate', 'clan'
+    reason TEXT NOT NULL,
+    items TEXT NOT NULL,
+    ingest_path VARCHAR(16) NOT NULL,   -- 'webhook' or 'channel'
+    queue_id INTEGER,                   -- ingest_queue row of the webhook payload
+    message_id INTEGER,                 -- Discord message the drop was read from
+    dropped_at INTEGER NOT NULL,        -- Unix timestamp of the drop
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_drop_rejections_created_at ON drop_rejections(created_at);
+CREATE INDEX idx_drop_rejections_reason_code ON drop_rejections(reason_code);
+CREATE INDEX idx_drop_rejections_player ON drop_rejections(player);
@@ -0,0 +1,16 @@
+-- Migration for the event time window and the pauses within it
+
+-- A single row holding the event window, either end may be left open
+CREATE TABLE event_schedule (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    starts_at INTEGER,                  -- Unix timestamp drops start counting from
+    ends_at INTEGER,                    -- Unix timestamp drops stop counting at
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Periods in which drops don't count, an open pause has no resumed_at
+CREATE TABLE event_pauses (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    paused_at INTEGER NOT NULL,
+    resumed_at INTEGER
+);
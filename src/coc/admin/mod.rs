@@ -0,0 +1,605 @@
+use std::fmt;
+
+use crate::coc::{self, database};
+use crate::dink::schedule;
+use crate::{Data, Error};
+
+/// Who performed an admin action, recorded in the audit trail
+#[derive(Clone)]
+pub struct Actor {
+    source: &'static str,
+    name: String,
+}
+
+impl Actor {
+    /// An owner running a Discord command
+    pub fn discord(user: &str) -> Self {
+        Actor {
+            source: "discord",
+            name: user.to_string(),
+        }
+    }
+
+    /// A caller of the admin API
+    pub fn api(caller: &str) -> Self {
+        Actor {
+            source: "api",
+            name: caller.to_string(),
+        }
+    }
+}
+
+/// Why an admin action was refused
+#[derive(Debug, Clone, PartialEq)]
+pub enum Refusal {
+    NotFound(String),
+    Conflict(String),
+    Invalid(String),
+}
+
+impl fmt::Display for Refusal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Refusal::NotFound(message) | Refusal::Conflict(message) | Refusal::Invalid(message) => {
+                write!(f, "{}", message)
+            }
+        }
+    }
+}
+
+/// Result of an admin action: a success message, or why it was refused
+pub type Outcome = Result<String, Refusal>;
+
+/// Records an admin action and its outcome in the audit trail
+async fn audit(
+    data: &Data,
+    actor: &Actor,
+    action: &str,
+    target: &str,
+    details: Option<String>,
+    outcome: &Outcome,
+) -> Result<(), Error> {
+    let outcome = match outcome {
+        Ok(_) => "ok".to_string(),
+        Err(refusal) => refusal.to_string(),
+    };
+
+    println!(
+        "[AUDIT] {} {} ran {} on '{}': {}",
+        actor.source, actor.name, action, target, outcome
+    );
+
+    database::insert_admin_audit(
+        &data.database,
+        actor.source,
+        &actor.name,
+        action,
+        target,
+        details.as_deref(),
+        &outcome,
+    )
+    .await
+}
+
+/// Creates a team with the starting set of buildings
+pub async fn add_team(
+    data: &Data,
+    actor: &Actor,
+    team_name: &str,
+    handicap: Option<i32>,
+) -> Result<Outcome, Error> {
+    let team_name = team_name.to_lowercase();
+    let handicap = handicap.unwrap_or(1);
+
+    let outcome = create_team(data, &team_name, handicap).await?;
+    audit(
+        data,
+        actor,
+        "add_team",
+        &team_name,
+        Some(format!("handicap={}", handicap)),
+        &outcome,
+    )
+    .await?;
+
+    Ok(outcome)
+}
+
+async fn create_team(data: &Data, team_name: &str, handicap: i32) -> Result<Outcome, Error> {
+    let pool = &data.database;
+
+    // Check if team with this name already exists
+    if database::get_team_by_name(pool, team_name).await?.is_some() {
+        return Ok(Err(Refusal::Conflict(format!(
+            "Team '{}' already exists!",
+            team_name
+        ))));
+    }
+
+    if !(1..=5).contains(&handicap) {
+        return Ok(Err(Refusal::Invalid(
+            "Handicap must be between 1 and 5.".to_string(),
+        )));
+    }
+
+    // Get the next available team ID
+    let next_id = database::get_max_team_id(pool).await? + 1;
+
+    // Insert the team into the database
+    database::insert_team(pool, next_id, team_name, handicap).await?;
+
+    // Get the next available building ID
+    let mut building_id = database::get_max_building_id(pool).await? + 1;
+
+    // Loop through the buildings and insert them into the database
+    for (building, config) in &data.town_config.assets {
+        database::insert_team_building(
+            pool,
+            building_id,
+            next_id,
+            building,
+            config.starting_level as i32,
+        )
+        .await?;
+        building_id += 1;
+    }
+
+    Ok(Ok(format!(
+        "Team '{}' created successfully! (ID: {}, Handicap: {})",
+        team_name, next_id, handicap
+    )))
+}
+
+/// Deletes a team and its buildings
+pub async fn remove_team(data: &Data, actor: &Actor, team_name: &str) -> Result<Outcome, Error> {
+    let pool = &data.database;
+    let team_name = team_name.to_lowercase();
+
+    let outcome = if database::get_team_by_name(pool, &team_name)
+        .await?
+        .is_none()
+    {
+        Err(Refusal::NotFound(format!(
+            "No team found with name '{}'",
+            team_name
+        )))
+    } else {
+        // Delete any buildings associated with this team first
+        database::delete_team_buildings(pool, &team_name).await?;
+        database::delete_team(pool, &team_name).await?;
+
+        Ok(format!(
+            "Team '{}' has been deleted successfully.",
+            team_name
+        ))
+    };
+
+    audit(data, actor, "remove_team", &team_name, None, &outcome).await?;
+
+    Ok(outcome)
+}
+
+/// Adds a player to a team
+pub async fn add_player(
+    data: &Data,
+    actor: &Actor,
+    username: &str,
+    team_name: &str,
+) -> Result<Outcome, Error> {
+    let pool = &data.database;
+    let username = username.to_lowercase();
+    let team_name = team_name.to_lowercase();
+
+    let outcome = match database::get_team_by_name(pool, &team_name).await? {
+        None => Err(Refusal::NotFound(format!(
+            "No team found with name '{}'",
+            team_name
+        ))),
+        Some(team_id) => {
+            if database::get_team_member(pool, team_id, &username)
+                .await?
+                .is_some()
+            {
+                Err(Refusal::Conflict(format!(
+                    "Player '{}' is already a member of team '{}'",
+                    username, team_name
+                )))
+            } else {
+                let next_id = database::get_max_team_member_id(pool).await? + 1;
+                database::insert_team_member(pool, next_id, team_id, &username).await?;
+
+                Ok(format!(
+                    "Player '{}' has been added to team '{}'!",
+                    username, team_name
+                ))
+            }
+        }
+    };
+
+    audit(
+        data,
+        actor,
+        "add_player",
+        &username,
+        Some(format!("team={}", team_name)),
+        &outcome,
+    )
+    .await?;
+
+    Ok(outcome)
+}
+
+/// Removes a player from all teams
+pub async fn remove_player(data: &Data, actor: &Actor, username: &str) -> Result<Outcome, Error> {
+    let pool = &data.database;
+    let username = username.to_lowercase();
+
+    let outcome = match database::get_user_team(pool, &username).await? {
+        None => Err(Refusal::NotFound(format!(
+            "Player '{}' is not a member of any team",
+            username
+        ))),
+        Some((_, team_name)) => {
+            database::delete_team_members(pool, &username).await?;
+
+            Ok(format!(
+                "Successfully removed player '{}' from team: {}",
+                username, team_name
+            ))
+        }
+    };
+
+    audit(data, actor, "remove_player", &username, None, &outcome).await?;
+
+    Ok(outcome)
+}
+
+/// Credits a resource to a team, applying the team's multipliers
+pub async fn force_insert_resource(
+    data: &Data,
+    actor: &Actor,
+    team_name: &str,
+    resource_name: &str,
+    quantity: i64,
+) -> Result<Outcome, Error> {
+    let team_name = team_name.to_lowercase();
+    let item_name = resource_name.to_lowercase();
+
+    let outcome = insert_resource(data, &team_name, &item_name, quantity).await?;
+    audit(
+        data,
+        actor,
+        "force_insert_resource",
+        &team_name,
+        Some(format!("resource={} quantity={}", item_name, quantity)),
+        &outcome,
+    )
+    .await?;
+
+    Ok(outcome)
+}
+
+async fn insert_resource(
+    data: &Data,
+    team_name: &str,
+    item_name: &str,
+    quantity: i64,
+) -> Result<Outcome, Error> {
+    let pool = &data.database;
+    let patterns = &data.res_patterns.resource_pattern;
+
+    let team_id = match database::get_team_by_name(pool, team_name).await? {
+        Some(id) => id,
+        None => {
+            return Ok(Err(Refusal::NotFound(format!(
+                "No team found with name '{}'",
+                team_name
+            ))))
+        }
+    };
+
+    // Check if item matches resource pattern
+    if !coc::patterns::matches_pattern(item_name, patterns) {
+        return Ok(Err(Refusal::Invalid(format!(
+            "Resource '{}' does not match any known resource pattern",
+            item_name
+        ))));
+    }
+
+    // get the category for the item
+    let category = coc::patterns::get_resource_category(item_name, patterns);
+
+    println!("Item match found with category: {}", category);
+    // get the modified resource amount
+    let quantity =
+        database::calculate_resource_total(pool, quantity as i32, team_id, &category).await?;
+
+    let mut conn = pool.acquire().await?;
+    let new_quantity =
+        database::add_resource_quantity(&mut conn, team_id, item_name, &category, quantity as i64)
+            .await?;
+
+    println!(
+        "Credited resource for team '{}': {} x {} (new total: {})",
+        team_name, item_name, quantity, new_quantity
+    );
+
+    Ok(Ok("Inserted resource successfully.".to_string()))
+}
+
+/// Raises a building one level without spending resources
+pub async fn force_upgrade_building(
+    data: &Data,
+    actor: &Actor,
+    team_name: &str,
+    building_name: &str,
+) -> Result<Outcome, Error> {
+    let team_name = team_name.to_lowercase();
+    let building_name = building_name.to_lowercase();
+
+    let outcome = change_building_level(data, &team_name, &building_name, 1).await?;
+    audit(
+        data,
+        actor,
+        "force_upgrade_building",
+        &team_name,
+        Some(format!("building={}", building_name)),
+        &outcome,
+    )
+    .await?;
+
+    Ok(outcome)
+}
+
+/// Lowers a building one level, down to its starting level
+pub async fn downgrade_building(
+    data: &Data,
+    actor: &Actor,
+    team_name: &str,
+    building_name: &str,
+) -> Result<Outcome, Error> {
+    let team_name = team_name.to_lowercase();
+    let building_name = building_name.to_lowercase();
+
+    let outcome = change_building_level(data, &team_name, &building_name, -1).await?;
+    audit(
+        data,
+        actor,
+        "downgrade_building",
+        &team_name,
+        Some(format!("building={}", building_name)),
+        &outcome,
+    )
+    .await?;
+
+    Ok(outcome)
+}
+
+/// Takes back exactly what a credited drop added, using its ledger entries.
+/// Also returns the team whose resources changed, so its embeds can be refreshed.
+pub async fn revert_drop(
+    data: &Data,
+    actor: &Actor,
+    drop_id: i64,
+) -> Result<(Outcome, Option<String>), Error> {
+    let (outcome, team_name) = undo_drop(data, actor, drop_id).await?;
+    audit(
+        data,
+        actor,
+        "revert_drop",
+        &format!("#{}", drop_id),
+        None,
+        &outcome,
+    )
+    .await?;
+
+    Ok((outcome, team_name))
+}
+
+async fn undo_drop(
+    data: &Data,
+    actor: &Actor,
+    drop_id: i64,
+) -> Result<(Outcome, Option<String>), Error> {
+    let pool = &data.database;
+
+    let reverted = database::revert_drop_ledger(pool, drop_id, &actor.name).await?;
+
+    let Some((team_name, player, source, _, _, _)) = reverted.first() else {
+        let refusal = match database::drop_ledger_exists(pool, drop_id).await? {
+            true => Refusal::Conflict(format!("Drop #{} was already reverted", drop_id)),
+            false => Refusal::NotFound(format!("No credited drop found with id #{}", drop_id)),
+        };
+        return Ok((Err(refusal), None));
+    };
+
+    let items = reverted
+        .iter()
+        .map(
+            |(_, _, _, item_name, credited, removed)| match credited == removed {
+                true => format!("-{} {}", removed, item_name),
+                false => format!(
+                    "-{} {} ({} credited, the rest was already spent)",
+                    removed, item_name, credited
+                ),
+            },
+        )
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let message = format!(
+        "Reverted drop #{} from {} ({}) for team '{}': {}",
+        drop_id, player, source, team_name, items
+    );
+
+    Ok((Ok(message), Some(team_name.clone())))
+}
+
+/// Sets when drops start and stop counting towards the event. Either end can
+/// be left open.
+pub async fn set_event_window(
+    data: &Data,
+    actor: &Actor,
+    starts_at: Option<i64>,
+    ends_at: Option<i64>,
+) -> Result<Outcome, Error> {
+    let outcome = match (starts_at, ends_at) {
+        (Some(start), Some(end)) if start >= end => Err(Refusal::Invalid(
+            "The event must start before it ends".to_string(),
+        )),
+        _ => {
+            database::set_event_window(&data.database, starts_at, ends_at).await?;
+            let time = |at: Option<i64>| at.map_or("open".to_string(), schedule::format_time);
+            Ok(format!(
+                "Event window set: starts {}, ends {}",
+                time(starts_at),
+                time(ends_at)
+            ))
+        }
+    };
+
+    audit(
+        data,
+        actor,
+        "set_event_window",
+        "event",
+        Some(format!("starts_at={:?} ends_at={:?}", starts_at, ends_at)),
+        &outcome,
+    )
+    .await?;
+
+    Ok(outcome)
+}
+
+/// Pauses the event, drops made until it's resumed aren't credited
+pub async fn pause_event(data: &Data, actor: &Actor) -> Result<Outcome, Error> {
+    let now = schedule::now();
+    let outcome = match database::start_event_pause(&data.database, now).await? {
+        true => Ok(format!("Event paused at {}", schedule::format_time(now))),
+        false => Err(Refusal::Conflict("The event is already paused".to_string())),
+    };
+    audit(data, actor, "pause_event", "event", None, &outcome).await?;
+
+    Ok(outcome)
+}
+
+/// Resumes a paused event
+pub async fn resume_event(data: &Data, actor: &Actor) -> Result<Outcome, Error> {
+    let now = schedule::now();
+    let outcome = match database::end_event_pause(&data.database, now).await? {
+        true => Ok(format!("Event resumed at {}", schedule::format_time(now))),
+        false => Err(Refusal::Conflict("The event isn't paused".to_string())),
+    };
+    audit(data, actor, "resume_event", "event", None, &outcome).await?;
+
+    Ok(outcome)
+}
+
+/// Moves a building up or down one level within its configured range
+async fn change_building_level(
+    data: &Data,
+    team_name: &str,
+    building_name: &str,
+    step: i64,
+) -> Result<Outcome, Error> {
+    let pool = &data.database;
+    let town_config = &data.town_config;
+
+    // Step 1: Check if the team exists
+    let team_id = match database::get_team_by_name(pool, team_name).await? {
+        Some(id) => id,
+        None => {
+            return Ok(Err(Refusal::NotFound(format!(
+                "No team found with name '{}'",
+                team_name
+            ))))
+        }
+    };
+
+    // Step 2: Check if the building exists in the configuration
+    let building_config = match town_config.assets.get(building_name) {
+        Some(config) => config,
+        None => {
+            return Ok(Err(Refusal::NotFound(format!(
+                "Building '{}' does not exist in the configuration. Available buildings: {}",
+                building_name,
+                town_config.get_building_types().join(", ")
+            ))))
+        }
+    };
+
+    // Step 3: Check if the team has this building and get its current level
+    let building = sqlx::query!(
+        r#"
+        SELECT id as "id: Option<i32>", level FROM team_buildings 
+        WHERE team_id = $1 AND building_name = $2
+        "#,
+        team_id,
+        building_name
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let (building_id, current_level) = match building {
+        Some(building) => (
+            building
+                .id
+                .ok_or_else(|| Error::from("Building ID is null"))?,
+            building.level,
+        ),
+        None => {
+            return Ok(Err(Refusal::NotFound(format!(
+                "Team '{}' doesn't have a '{}' building. Please check the building name.",
+                team_name, building_name
+            ))))
+        }
+    };
+
+    // Step 4: Check the new level stays within the building's range
+    if step > 0 && current_level as u32 >= building_config.max_level {
+        return Ok(Err(Refusal::Invalid(format!(
+            "Building '{}' is already at its maximum level ({})!",
+            building_name, current_level
+        ))));
+    }
+    if step < 0 && current_level <= building_config.starting_level as i64 {
+        return Ok(Err(Refusal::Invalid(format!(
+            "Building '{}' is already at its starting level ({}) and cannot be downgraded further!",
+            building_name, current_level
+        ))));
+    }
+
+    // Step 5: Change the building level
+    let target_level = current_level + step;
+    sqlx::query!(
+        r#"
+        UPDATE team_buildings
+        SET level = $1
+        WHERE id = $2
+        "#,
+        target_level,
+        building_id
+    )
+    .execute(pool)
+    .await?;
+
+    let icon = if !building_config.icon.is_empty() {
+        format!("{} ", building_config.icon)
+    } else {
+        String::new()
+    };
+
+    let message = if step > 0 {
+        format!(
+            "{}**{}** upgraded to level **{}** for team **{}**!\n\n**Resources used:**\n None.",
+            icon, building_config.name, target_level, team_name
+        )
+    } else {
+        format!(
+            "{}**{}** downgraded to level **{}** for team **{}**!",
+            icon, building_config.name, target_level, team_name
+        )
+    };
+
+    Ok(Ok(message))
+}
@@ -25,12 +25,51 @@ pub enum UpgradeCost {
     Category(String, u32), // (category_name, amount)
 }
 
+/// A drop source outside the bestiary and the rule gating its loot.
+/// Sources without a building or categories are always allowed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceAccess {
+    pub source: String,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// Building whose level unlocks the source
+    pub building: Option<String>,
+    #[serde(default)]
+    pub min_level: u32,
+    /// Resource categories credited from the source, empty for all of them
+    #[serde(default)]
+    pub categories: Vec<String>,
+}
+
+impl SourceAccess {
+    /// Whether a drop source names this entry, ignoring case
+    pub fn matches(&self, source: &str) -> bool {
+        let source = source.trim();
+        self.source.eq_ignore_ascii_case(source)
+            || self
+                .aliases
+                .iter()
+                .any(|alias| alias.eq_ignore_ascii_case(source))
+    }
+
+    /// Whether items of a resource category are credited from this source
+    pub fn allows_category(&self, category: &str) -> bool {
+        self.categories.is_empty()
+            || self
+                .categories
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(category))
+    }
+}
+
 /// All buildings configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TownConfig {
     pub assets: HashMap<String, BuildingConfig>,
     #[serde(default)]
     pub resources: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    pub source_access: Vec<SourceAccess>,
 }
 
 impl TownConfig {
@@ -45,8 +84,100 @@ impl TownConfig {
         // Convert from raw format to our format
         let assets = config.assets;
         let resources = config.resources.unwrap_or_default();
+        let source_access = config.source_access.unwrap_or_default();
 
-        Ok(TownConfig { assets, resources })
+        let town_config = TownConfig {
+            assets,
+            resources,
+            source_access,
+        };
+        town_config.validate_source_access()?;
+
+        Ok(town_config)
+    }
+
+    /// Check every source access entry names a building and a level it can reach,
+    /// and that no source name is claimed twice
+    fn validate_source_access(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut seen: HashMap<String, &str> = HashMap::new();
+
+        for access in &self.source_access {
+            match &access.building {
+                Some(building_type) => {
+                    let building = self.assets.get(building_type).ok_or_else(|| {
+                        format!(
+                            "Source '{}' requires unknown building '{}'",
+                            access.source, building_type
+                        )
+                    })?;
+
+                    if access.min_level > building.max_level {
+                        return Err(format!(
+                            "Source '{}' requires {} level {}, but its max level is {}",
+                            access.source, building.name, access.min_level, building.max_level
+                        )
+                        .into());
+                    }
+                }
+                None if access.min_level > 0 => {
+                    return Err(format!(
+                        "Source '{}' has a min_level but no building",
+                        access.source
+                    )
+                    .into());
+                }
+                None => {}
+            }
+
+            for name in std::iter::once(&access.source).chain(&access.aliases) {
+                if let Some(other) = seen.insert(name.to_lowercase(), &access.source) {
+                    return Err(format!(
+                        "Source name '{}' is used by both '{}' and '{}'",
+                        name, other, access.source
+                    )
+                    .into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the category whitelists only name categories from the resource patterns
+    pub fn validate_source_categories(&self, known: &[&str]) -> Result<(), String> {
+        for access in &self.source_access {
+            for category in &access.categories {
+                if !known
+                    .iter()
+                    .any(|known| known.eq_ignore_ascii_case(category))
+                {
+                    return Err(format!(
+                        "Source '{}' allows unknown resource category '{}'",
+                        access.source, category
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Find the access rule for a drop source, if it has one
+    pub fn get_source_access(&self, source: &str) -> Option<&SourceAccess> {
+        self.source_access
+            .iter()
+            .find(|access| access.matches(source))
+    }
+
+    /// Sources a building unlocks at the given level, in config order
+    pub fn get_unlocked_sources(&self, building_type: &str, level: u32) -> Vec<&str> {
+        self.source_access
+            .iter()
+            .filter(|access| {
+                access.building.as_deref() == Some(building_type) && access.min_level <= level
+            })
+            .map(|access| access.source.as_str())
+            .collect()
     }
 
     /// Get all building types
@@ -132,6 +263,7 @@ impl TownConfig {
 struct TownConfigRaw {
     assets: HashMap<String, BuildingConfig>,
     resources: Option<HashMap<String, Vec<String>>>,
+    source_access: Option<Vec<SourceAccess>>,
 }
 
 /// Initialize the building configuration
@@ -179,6 +311,43 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_source_access() {
+        let config = init_assets().unwrap();
+
+        let toa = config
+            .get_source_access("tombs of amascut: expert mode")
+            .unwrap();
+        assert_eq!(toa.source, "Tombs of Amascut");
+        assert_eq!(toa.building.as_deref(), Some("garrisons"));
+        assert!(config.get_source_access("Man").is_none());
+
+        // Every raid is reachable once garrisons is maxed
+        let garrisons = &config.assets["garrisons"];
+        assert_eq!(
+            config
+                .get_unlocked_sources("garrisons", garrisons.max_level)
+                .len(),
+            5
+        );
+
+        // Skilling sources credit only their whitelisted categories
+        let wintertodt = config.get_source_access("supply crate").unwrap();
+        assert!(wintertodt.building.is_none());
+        assert!(wintertodt.allows_category("logs"));
+        assert!(!wintertodt.allows_category("coins"));
+        assert!(config
+            .get_source_access("Reward casket (elite)")
+            .unwrap()
+            .allows_category("coins"));
+
+        let mut invalid = config.clone();
+        invalid.source_access[0].min_level = garrisons.max_level + 1;
+        assert!(invalid.validate_source_access().is_err());
+
+        assert!(config.validate_source_categories(&["logs"]).is_err());
+    }
+
     #[test]
     fn test_parse_category_costs() {
         let result = init_assets();
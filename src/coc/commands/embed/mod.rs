@@ -1,3 +1,4 @@
+use crate::dink::schedule::EventSchedule;
 use crate::{Data, Error};
 
 use ::serenity::all::CreateEmbed;
@@ -275,18 +276,19 @@ pub async fn get_buildings_embed(
             }
         }
 
-        // Special handling for Garrisons (Raid Access)
-        if building_key == "garrisons" {
-            let raid_access = match building.level {
-                2 => "Access to: Lunar Chests",
-                3 => "Access to: Lunar Chests, Colosseum",
-                4 => "Access to: Lunar Chests, Colosseum, ToA",
-                5 => "Access to: Lunar Chests, Colosseum, ToA and CoX",
-                6 => "Access to: Lunar Chests, Colosseum + all raids",
-                _ => "No special content access",
+        // Sources unlocked by this building (e.g. raids from Garrisons)
+        let unlocked = town_config.get_unlocked_sources(&building_key, building.level as u32);
+        if town_config
+            .source_access
+            .iter()
+            .any(|access| access.building.as_deref() == Some(building_key.as_str()))
+        {
+            let source_access = match unlocked.is_empty() {
+                true => "No special content access".to_string(),
+                false => format!("Access to: {}", unlocked.join(", ")),
             };
 
-            building_entry.push_str(&format!("\n   ┗ **Raid Access**: {}\n", raid_access));
+            building_entry.push_str(&format!("\n   ┗ **Source Access**: {}\n", source_access));
         }
 
         // Add bonus information if this building provides any
@@ -452,6 +454,12 @@ pub async fn get_teams_townhall_levels(data: &Data) -> Result<Option<CreateEmbed
         table = "```\nNo teams with town halls found.\n```".to_string();
     }
 
+    // Show when drops count, if an event window or pause is set
+    let schedule = EventSchedule::load(pool).await?;
+    if let Some(window) = schedule.describe() {
+        embed = embed.field("📅 Event", window, false);
+    }
+
     // Add the table to the embed
     embed = embed.field("Town Hall Rankings", table, false);
 
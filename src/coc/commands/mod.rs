@@ -1,9 +1,9 @@
 use crate::{
     coc::{
-        self,
-        database::{get_resource_quantity_by_name, insert_new_resource, update_resource_quantity},
+        admin::{self, Actor, Outcome, Refusal},
         get_team,
     },
+    dink::schedule,
     Context, Data, Error,
 };
 
@@ -12,6 +12,34 @@ use poise::serenity_prelude as serenity;
 mod embed;
 pub mod helper;
 
+/// Replies with the result of an admin action. Refusals are only shown to
+/// the caller, successes are announced publicly when `public` is set.
+async fn reply_outcome(ctx: Context<'_>, outcome: Outcome, public: bool) -> Result<(), Error> {
+    match outcome {
+        Ok(message) if public => {
+            ctx.say(message).await?;
+        }
+        Ok(message) => {
+            ctx.send(
+                poise::CreateReply::default()
+                    .content(message)
+                    .ephemeral(true),
+            )
+            .await?;
+        }
+        Err(refusal) => {
+            ctx.send(
+                poise::CreateReply::default()
+                    .content(refusal.to_string())
+                    .ephemeral(true),
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
 /// Lists all teams in the database
 #[poise::command(
     slash_command,
@@ -65,184 +93,154 @@ pub async fn add_team(
     #[description = "Name of the team to create"] team_name: String,
     #[description = "Handicap value for the team (default: 1)"] handicap: Option<i32>,
 ) -> Result<(), Error> {
-    // Get database connection from context data
-    let pool = &ctx.data().database;
-
-    // Convert team name to lowercase
-    let team_name = team_name.to_lowercase();
+    let actor = Actor::discord(&ctx.author().name);
+    let outcome = admin::add_team(ctx.data(), &actor, &team_name, handicap).await?;
 
-    println!("checking if team exists");
+    // Successful creation is a public notification
+    reply_outcome(ctx, outcome, true).await
+}
 
-    // Check if team with this name already exists using the database function
-    if let Some(_) = crate::coc::database::get_team_by_name(pool, &team_name).await? {
-        ctx.send(
-            poise::CreateReply::default()
-                .content(format!("Team '{}' already exists!", team_name))
-                .ephemeral(true),
-        )
-        .await?;
-        return Ok(());
-    }
+/// Deletes a team from the database by name
+#[poise::command(slash_command, prefix_command, guild_only, owners_only)]
+pub async fn remove_team(
+    ctx: Context<'_>,
+    #[description = "Name of the team to delete"] team_name: String,
+) -> Result<(), Error> {
+    let actor = Actor::discord(&ctx.author().name);
+    let outcome = admin::remove_team(ctx.data(), &actor, &team_name).await?;
 
-    println!("team does not exist");
-    println!("creating team");
+    // Use a public notification for successful team deletion
+    reply_outcome(ctx, outcome, true).await
+}
 
-    // Get the next available team ID
-    let next_id = crate::coc::database::get_max_team_id(pool).await? + 1;
-    println!("next id: {}", next_id);
+/// Adds a player to a team
+#[poise::command(slash_command, prefix_command, guild_only, owners_only)]
+pub async fn add_player(
+    ctx: Context<'_>,
+    #[description = "Username of the player"] username: String,
+    #[description = "Name of the team"] team_name: String,
+) -> Result<(), Error> {
+    let actor = Actor::discord(&ctx.author().name);
+    let outcome = admin::add_player(ctx.data(), &actor, &username, &team_name).await?;
 
-    // Use provided handicap or default to 1
-    let handicap = handicap.unwrap_or(1);
+    // Use public message for successful addition
+    reply_outcome(ctx, outcome, true).await
+}
 
-    if handicap < 1 || handicap > 5 {
-        ctx.send(
-            poise::CreateReply::default()
-                .content("Handicap must be between 1 and 5.")
-                .ephemeral(true),
-        )
-        .await?;
-        return Ok(());
-    }
+/// Removes a player from all teams
+#[poise::command(slash_command, prefix_command, guild_only, owners_only)]
+pub async fn remove_player(
+    ctx: Context<'_>,
+    #[description = "Username of the player to remove"] username: String,
+) -> Result<(), Error> {
+    let actor = Actor::discord(&ctx.author().name);
+    let outcome = admin::remove_player(ctx.data(), &actor, &username).await?;
 
-    // Insert the team into the database
-    crate::coc::database::insert_team(pool, next_id, &team_name, handicap).await?;
+    // Public message for successful removal
+    reply_outcome(ctx, outcome, true).await
+}
 
-    // Insert a new set of buildings into the database
-    let town_config = &ctx.data().town_config;
+/// Binds a player to the Dink account hash seen on their first drop
+#[poise::command(slash_command, prefix_command, guild_only, owners_only)]
+pub async fn confirm_account(
+    ctx: Context<'_>,
+    #[description = "Username of the player"] username: String,
+) -> Result<(), Error> {
+    // Get database connection from context data
+    let pool = &ctx.data().database;
 
-    // print the starting level of the buildings
-    for (building, config) in &town_config.assets {
-        println!("{}: {}", building, config.starting_level);
-    }
+    // Convert username to lowercase
+    let username = username.to_lowercase();
 
-    // Get the next available building ID
-    let mut building_id = crate::coc::database::get_max_building_id(pool).await? + 1;
-
-    // Loop through the buildings and insert them into the database
-    for (building, config) in &town_config.assets {
-        crate::coc::database::insert_team_building(
-            pool,
-            building_id,
-            next_id,
-            &building,
-            config.starting_level as i32,
-        )
-        .await?;
-        building_id += 1;
+    match crate::coc::database::confirm_account_hash(pool, &username).await? {
+        Some(account_hash) => {
+            ctx.say(format!(
+                "Player '{}' is now bound to account `{}`. Drops from other accounts using this name will not be credited.",
+                username, account_hash
+            ))
+            .await?;
+        }
+        None => {
+            ctx.send(
+                poise::CreateReply::default()
+                    .content(format!(
+                        "Player '{}' has no account waiting for confirmation. A drop must be received from them first.",
+                        username
+                    ))
+                    .ephemeral(true),
+            )
+            .await?;
+        }
     }
 
-    // This message should be public as it's a positive notification
-    ctx.say(format!(
-        "Team '{}' created successfully! (ID: {}, Handicap: {})",
-        team_name, next_id, handicap
-    ))
-    .await?;
     Ok(())
 }
 
-/// Deletes a team from the database by name
+/// Removes a player's account binding so it can be confirmed again
 #[poise::command(slash_command, prefix_command, guild_only, owners_only)]
-pub async fn remove_team(
+pub async fn unbind_account(
     ctx: Context<'_>,
-    #[description = "Name of the team to delete"] team_name: String,
+    #[description = "Username of the player"] username: String,
 ) -> Result<(), Error> {
     // Get database connection from context data
     let pool = &ctx.data().database;
 
-    // Convert team name to lowercase
-    let team_name = team_name.to_lowercase();
+    // Convert username to lowercase
+    let username = username.to_lowercase();
 
-    // Check if team with this name exists
-    if let None = crate::coc::database::get_team_by_name(pool, &team_name).await? {
+    if crate::coc::database::clear_account_hash(pool, &username).await? {
+        ctx.say(format!(
+            "Removed the account binding for player '{}'",
+            username
+        ))
+        .await?;
+    } else {
         ctx.send(
             poise::CreateReply::default()
-                .content(format!("No team found with name '{}'", team_name))
+                .content(format!("Player '{}' is not a member of any team", username))
                 .ephemeral(true),
         )
         .await?;
-        return Ok(());
     }
 
-    // Delete any buildings associated with this team first
-    crate::coc::database::delete_team_buildings(pool, &team_name).await?;
-
-    // Delete the team from the database
-    crate::coc::database::delete_team(pool, &team_name).await?;
-
-    // Use a public notification for successful team deletion
-    ctx.say(format!(
-        "Team '{}' has been deleted successfully.",
-        team_name,
-    ))
-    .await?;
-
     Ok(())
 }
 
-/// Adds a player to a team
+/// Lets a player outside the event clans take part
 #[poise::command(slash_command, prefix_command, guild_only, owners_only)]
-pub async fn add_player(
+pub async fn add_clan_guest(
     ctx: Context<'_>,
     #[description = "Username of the player"] username: String,
-    #[description = "Name of the team"] team_name: String,
 ) -> Result<(), Error> {
     // Get database connection from context data
     let pool = &ctx.data().database;
 
-    // Convert username and team name to lowercase
+    // Convert username to lowercase
     let username = username.to_lowercase();
-    let team_name = team_name.to_lowercase();
-
-    // Check if the team exists
-    let team_id = match crate::coc::database::get_team_by_name(pool, &team_name).await? {
-        Some(id) => id,
-        None => {
-            ctx.send(
-                poise::CreateReply::default()
-                    .content(format!("No team found with name '{}'", team_name))
-                    .ephemeral(true),
-            )
-            .await?;
-            return Ok(());
-        }
-    };
 
-    // Check if the player is already on this team
-    if let Some(_) = crate::coc::database::get_team_member(pool, team_id, &username).await? {
+    if crate::coc::database::add_clan_guest(pool, &username).await? {
+        ctx.say(format!(
+            "Player '{}' can now take part as a guest",
+            username
+        ))
+        .await?;
+    } else {
         ctx.send(
             poise::CreateReply::default()
-                .content(format!(
-                    "Player '{}' is already a member of team '{}'",
-                    username, team_name
-                ))
+                .content(format!("Player '{}' is already a guest", username))
                 .ephemeral(true),
         )
         .await?;
-        return Ok(());
     }
 
-    // Get the next available member ID
-    let next_id = crate::coc::database::get_max_team_member_id(pool).await? + 1;
-    println!("next id: {}", next_id);
-
-    // Add the player to the team
-    crate::coc::database::insert_team_member(pool, next_id, team_id, &username).await?;
-
-    // Use public message for successful addition
-    ctx.say(format!(
-        "Player '{}' has been added to team '{}'!",
-        username, team_name
-    ))
-    .await?;
-
     Ok(())
 }
 
-/// Removes a player from all teams
+/// Removes a player from the clan guest list
 #[poise::command(slash_command, prefix_command, guild_only, owners_only)]
-pub async fn remove_player(
+pub async fn remove_clan_guest(
     ctx: Context<'_>,
-    #[description = "Username of the player to remove"] username: String,
+    #[description = "Username of the player"] username: String,
 ) -> Result<(), Error> {
     // Get database connection from context data
     let pool = &ctx.data().database;
@@ -250,33 +248,18 @@ pub async fn remove_player(
     // Convert username to lowercase
     let username = username.to_lowercase();
 
-    // Check if player exists in any team
-    let team_info = crate::coc::database::get_user_team(pool, &username).await?;
-
-    // If player is not in any team, inform the user with ephemeral message
-    if team_info.is_none() {
+    if crate::coc::database::remove_clan_guest(pool, &username).await? {
+        ctx.say(format!("Player '{}' is no longer a guest", username))
+            .await?;
+    } else {
         ctx.send(
             poise::CreateReply::default()
-                .content(format!("Player '{}' is not a member of any team", username))
+                .content(format!("Player '{}' is not a guest", username))
                 .ephemeral(true),
         )
         .await?;
-        return Ok(());
     }
 
-    // Get team name for feedback message
-    let (_, team_name) = team_info.unwrap();
-
-    // Remove the player from all teams
-    crate::coc::database::delete_team_members(pool, &username).await?;
-
-    // Public message for successful removal
-    ctx.say(format!(
-        "Successfully removed player '{}' from team: {}",
-        username, team_name
-    ))
-    .await?;
-
     Ok(())
 }
 
@@ -428,7 +411,7 @@ pub async fn list_team_resources(
 
 /// Updates all registered embeds for a specific team
 pub async fn update_team_embeds(
-    ctx: &serenity::Context,
+    http: &serenity::Http,
     data: &Data,
     team_name: &str,
 ) -> Result<(usize, Vec<String>), Error> {
@@ -493,7 +476,7 @@ pub async fn update_team_embeds(
         // Try to edit the message
         let result = channel_id
             .edit_message(
-                &ctx.http,
+                http,
                 message_id,
                 serenity::builder::EditMessage::new().embed(embed),
             )
@@ -501,6 +484,7 @@ pub async fn update_team_embeds(
 
         match result {
             Ok(_) => {
+                data.metrics.embed_edited();
                 updated_count += 1;
                 results.push(format!(
                     "Updated {} embed in channel {}",
@@ -508,6 +492,7 @@ pub async fn update_team_embeds(
                 ));
             }
             Err(err) => {
+                data.metrics.discord_error();
                 results.push(format!(
                     "Failed to update {} embed in channel {}: {}",
                     variant, channel_id, err
@@ -537,85 +522,178 @@ pub async fn force_insert_resource(
     #[description = "Name of the resource to insert"] resource_name: String,
     #[description = "Amount of the resource to insert"] quantity: i64,
 ) -> Result<(), Error> {
-    // Get database connection from context data
-    let pool = &ctx.data().database;
-    let patterns = &ctx.data().res_patterns.resource_pattern;
+    let actor = Actor::discord(&ctx.author().name);
+    let outcome =
+        admin::force_insert_resource(ctx.data(), &actor, &team_name, &resource_name, quantity)
+            .await?;
 
-    // Convert inputs to lowercase for consistent lookups
-    let team_name = team_name.to_lowercase();
-    let item_name = resource_name.to_lowercase();
+    if outcome.is_ok() {
+        // also update any embed resource messages
+        let _ = update_team_embeds(ctx.http(), ctx.data(), &team_name.to_lowercase()).await?;
+    }
 
-    // Check if the team exists
-    let team_id = match crate::coc::database::get_team_by_name(pool, &team_name).await? {
-        Some(id) => id,
-        None => {
-            ctx.send(
-                poise::CreateReply::default()
-                    .content(format!("No team found with name '{}'", team_name))
-                    .ephemeral(true),
-            )
-            .await?;
-            return Ok(());
+    reply_outcome(ctx, outcome, false).await
+}
+
+/// Admin Command to undo everything a credited drop added to a team
+#[poise::command(slash_command, prefix_command, guild_only, owners_only)]
+pub async fn revert_drop(
+    ctx: Context<'_>,
+    #[description = "Id of the drop, as shown when it was credited"] drop_id: i64,
+) -> Result<(), Error> {
+    let actor = Actor::discord(&ctx.author().name);
+    let (outcome, team_name) = admin::revert_drop(ctx.data(), &actor, drop_id).await?;
+
+    if let Some(team_name) = team_name {
+        let _ = update_team_embeds(ctx.http(), ctx.data(), &team_name).await?;
+    }
+
+    reply_outcome(ctx, outcome, true).await
+}
+
+/// Admin Command to set when drops count towards the event
+#[poise::command(slash_command, prefix_command, guild_only, owners_only)]
+pub async fn set_event_window(
+    ctx: Context<'_>,
+    #[description = "Start in UTC, like 2025-01-31 18:00, or leave empty for no start"]
+    starts_at: Option<String>,
+    #[description = "End in UTC, like 2025-02-14 18:00, or leave empty for no end"] ends_at: Option<
+        String,
+    >,
+) -> Result<(), Error> {
+    let parse = |time: &Option<String>| match time {
+        Some(time) => schedule::parse_time(time).map(Some).ok_or_else(|| {
+            Refusal::Invalid(format!(
+                "Couldn't read '{}' as a time, use YYYY-MM-DD HH:MM in UTC or a unix timestamp",
+                time
+            ))
+        }),
+        None => Ok(None),
+    };
+
+    let actor = Actor::discord(&ctx.author().name);
+    let outcome = match (parse(&starts_at), parse(&ends_at)) {
+        (Ok(starts_at), Ok(ends_at)) => {
+            admin::set_event_window(ctx.data(), &actor, starts_at, ends_at).await?
         }
+        (Err(refusal), _) | (_, Err(refusal)) => Err(refusal),
     };
 
-    // Check if item matches resource pattern
-    let result = coc::patterns::matches_pattern(&item_name, patterns);
+    if outcome.is_ok() {
+        let _ = update_global_embeds(ctx.http(), ctx.data(), Some("townhall_ranking")).await?;
+    }
 
-    // Skip this item if it doesn't match any resource pattern
-    if !result {
-        ctx.send(
-            poise::CreateReply::default()
-                .content(format!(
-                    "Resource '{}' does not match any known resource pattern",
-                    item_name
-                ))
-                .ephemeral(true),
-        )
-        .await?;
-        return Ok(());
+    reply_outcome(ctx, outcome, true).await
+}
+
+/// Admin Command to pause the event, drops aren't credited until it resumes
+#[poise::command(slash_command, prefix_command, guild_only, owners_only)]
+pub async fn pause_event(ctx: Context<'_>) -> Result<(), Error> {
+    let actor = Actor::discord(&ctx.author().name);
+    let outcome = admin::pause_event(ctx.data(), &actor).await?;
+
+    if outcome.is_ok() {
+        let _ = update_global_embeds(ctx.http(), ctx.data(), Some("townhall_ranking")).await?;
     }
 
-    // get the category for the item
-    let category = coc::patterns::get_resource_category(&item_name, patterns);
+    reply_outcome(ctx, outcome, true).await
+}
 
-    println!("Item match found with category: {}", category);
-    // get the modified resource amount
-    let quantity =
-        coc::database::calculate_resource_total(pool, quantity as i32, team_id, &category).await?;
+/// Admin Command to resume a paused event
+#[poise::command(slash_command, prefix_command, guild_only, owners_only)]
+pub async fn resume_event(ctx: Context<'_>) -> Result<(), Error> {
+    let actor = Actor::discord(&ctx.author().name);
+    let outcome = admin::resume_event(ctx.data(), &actor).await?;
 
-    // Check if this resource already exists for the team
-    let existing_resource = get_resource_quantity_by_name(pool, team_id, &item_name).await?;
-    // println!("Existing resource: {:?}", existing_resource);
+    if outcome.is_ok() {
+        let _ = update_global_embeds(ctx.http(), ctx.data(), Some("townhall_ranking")).await?;
+    }
+
+    reply_outcome(ctx, outcome, true).await
+}
+
+/// Cuts a reply down to Discord's message length limit
+fn fit_message(content: String) -> String {
+    const LIMIT: usize = 2000;
 
-    match existing_resource {
-        Some(resource) => {
-            // Update quantity of existing resource
-            let new_quantity = resource + quantity as i64;
-            update_resource_quantity(pool, team_id, &item_name, new_quantity).await?;
+    if content.chars().count() <= LIMIT {
+        return content;
+    }
+
+    let mut content: String = content.chars().take(LIMIT - 1).collect();
+    content.push('…');
+    content
+}
 
-            println!(
-                "Updated resource quantity for team '{}': {} x {} (new total: {})",
-                team_name, item_name, quantity, new_quantity
-            );
+/// Explains how a drop would be credited, without crediting it
+#[poise::command(slash_command, prefix_command, guild_only)]
+pub async fn explain_drop(
+    ctx: Context<'_>,
+    #[description = "Player who got the drop"] player: String,
+    #[description = "Monster or activity the drop came from"] source: String,
+    #[description = "Name of the item"] item: String,
+    #[description = "Quantity, e.g. 1,000 or 1.5K (default: 1)"] quantity: Option<String>,
+) -> Result<(), Error> {
+    let quantity = match crate::dink::parser::parse_quantity(quantity.as_deref().unwrap_or("1")) {
+        Ok(quantity) => quantity,
+        Err(e) => {
+            ctx.send(
+                poise::CreateReply::default()
+                    .content(e.to_string())
+                    .ephemeral(true),
+            )
+            .await?;
+            return Ok(());
         }
-        None => {
-            // Insert new resource entry
-            insert_new_resource(pool, team_id, &item_name, &category, quantity as i64).await?;
+    };
+
+    let drop = crate::dink::DinkDrop::new(player, source, vec![(item, quantity)]);
+    let report = crate::dink::evaluate::explain_drop(ctx.data(), &drop).await?;
 
-            println!(
-                "Added new resource for team '{}': {} x {}",
-                team_name, item_name, quantity
-            );
+    ctx.send(
+        poise::CreateReply::default()
+            .content(fit_message(report))
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Explains how the drops in a Dink message would be credited, without crediting them
+#[poise::command(context_menu_command = "Explain drop", guild_only)]
+pub async fn explain_drop_message(
+    ctx: Context<'_>,
+    #[description = "Dink loot message"] message: serenity::Message,
+) -> Result<(), Error> {
+    let mut reports = Vec::new();
+
+    for embed in &message.embeds {
+        let author = embed.author.as_ref().map(|author| author.name.as_str());
+        let fields: Vec<(&str, &str)> = embed
+            .fields
+            .iter()
+            .map(|field| (field.name.as_str(), field.value.as_str()))
+            .collect();
+        let description = embed.description.as_deref().unwrap_or_default();
+
+        match crate::dink::parser::parse_loot_embed(author, description, &fields) {
+            Ok(drops) => {
+                for drop in drops {
+                    reports.push(crate::dink::evaluate::explain_drop(ctx.data(), &drop).await?);
+                }
+            }
+            Err(e) => reports.push(format!("Could not read the loot: {}", e)),
         }
     }
 
-    // also update any embed resource messages
-    let _ = update_team_embeds(ctx.serenity_context(), ctx.data(), &team_name).await?;
+    if reports.is_empty() {
+        reports.push("This message has no Dink loot embed.".to_string());
+    }
 
     ctx.send(
         poise::CreateReply::default()
-            .content(format!("Inserted resource successfully."))
+            .content(fit_message(reports.join("\n")))
             .ephemeral(true),
     )
     .await?;
@@ -623,146 +701,159 @@ pub async fn force_insert_resource(
     Ok(())
 }
 
-/// Admin Command to Force Upgrade a building for a team
+/// Admin Command to list rejected drops, filtered by reason, player or age
 #[poise::command(slash_command, prefix_command, guild_only, owners_only)]
-pub async fn force_upgrade_building(
+pub async fn list_rejected_drops(
     ctx: Context<'_>,
-    #[description = "Name of the team"] team_name: String,
-    #[description = "Name of the building to upgrade"] building_name: String,
+    #[description = "Reason, e.g. slayer level, combat level, building, duplicate, clan"]
+    reason: Option<String>,
+    #[description = "Only drops from this player"] player: Option<String>,
+    #[description = "Only drops from the last number of hours"] hours: Option<i64>,
+    #[description = "Number of entries to show (default: 10)"] limit: Option<i64>,
 ) -> Result<(), Error> {
-    // Get database connection and configs from context data
+    // Get database connection from context data
     let pool = &ctx.data().database;
-    let town_config = &ctx.data().town_config;
 
-    // Convert inputs to lowercase for consistent lookups
-    let team_name = team_name.to_lowercase();
-    let building_name = building_name.to_lowercase();
-
-    // Step 1: Check if the team exists
-    let team = sqlx::query!(
-        r#"
-        SELECT id as "id: Option<i32>" FROM teams WHERE name = $1
-        "#,
-        team_name
-    )
-    .fetch_optional(pool)
-    .await?;
+    let limit = limit.unwrap_or(10).clamp(1, 25);
 
-    let team_id = match team {
-        Some(team) => team.id.ok_or_else(|| Error::from("Team ID is null"))?,
-        None => {
+    // Accept reasons as written, e.g. "slayer level" for slayer_level
+    let reason_code = reason.map(|reason| reason.trim().to_lowercase().replace([' ', '-'], "_"));
+    if let Some(code) = &reason_code {
+        if !crate::dink::RejectReason::CODES.contains(&code.as_str()) {
             ctx.send(
                 poise::CreateReply::default()
-                    .content(format!("No team found with name '{}'", team_name))
+                    .content(format!(
+                        "Unknown reason '{}'. Reasons: {}",
+                        code,
+                        crate::dink::RejectReason::CODES.join(", ")
+                    ))
                     .ephemeral(true),
             )
             .await?;
             return Ok(());
         }
-    };
+    }
 
-    // Step 2: Check if the building exists in the configuration
-    if !town_config.assets.contains_key(&building_name) {
+    let rejections = crate::coc::database::get_drop_rejections(
+        pool,
+        reason_code.as_deref(),
+        player.as_deref(),
+        hours.filter(|hours| *hours > 0),
+        limit,
+    )
+    .await?;
+
+    if rejections.is_empty() {
         ctx.send(
             poise::CreateReply::default()
-                .content(format!(
-                    "Building '{}' does not exist in the configuration. Available buildings: {}",
-                    building_name,
-                    town_config.get_building_types().join(", ")
-                ))
+                .content("No rejected drops match.")
                 .ephemeral(true),
         )
         .await?;
         return Ok(());
     }
 
-    // Step 3: Check if the team has this building and get its current level
-    let building = sqlx::query!(
-        r#"
-        SELECT id as "id: Option<i32>", level FROM team_buildings 
-        WHERE team_id = $1 AND building_name = $2
-        "#,
-        team_id,
-        building_name
+    // Format the results
+    let response = rejections
+        .iter()
+        .map(
+            |(id, player, team_name, source, reason, items, reference, created_at)| {
+                format!(
+                    "• **#{}** {} ({}) — {} from {} at {}\n  {} [{}]",
+                    id,
+                    player,
+                    team_name.as_deref().unwrap_or("no team"),
+                    items,
+                    source,
+                    created_at,
+                    reason,
+                    reference
+                )
+            },
+        )
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    ctx.send(
+        poise::CreateReply::default()
+            .content(fit_message(format!("**Rejected drops:**\n{}", response)))
+            .ephemeral(true),
     )
-    .fetch_optional(pool)
     .await?;
 
-    let (building_id, current_level) = match building {
-        Some(building) => (
-            building
-                .id
-                .ok_or_else(|| Error::from("Building ID is null"))?,
-            building.level,
-        ),
-        None => {
-            ctx.send(
-                poise::CreateReply::default()
-                    .content(format!(
-                        "Team '{}' doesn't have a '{}' building. Please check the building name.",
-                        team_name, building_name
-                    ))
-                    .ephemeral(true),
-            )
-            .await?;
-            return Ok(());
-        }
-    };
+    Ok(())
+}
 
-    // Step 4: Check if building is at max level
-    let building_config = &town_config.assets[&building_name];
-    if current_level as u32 >= building_config.max_level {
+/// Admin Command to list drops that were skipped as duplicates
+#[poise::command(slash_command, prefix_command, guild_only, owners_only)]
+pub async fn list_duplicate_drops(
+    ctx: Context<'_>,
+    #[description = "Number of entries to show (default: 10)"] limit: Option<i64>,
+) -> Result<(), Error> {
+    // Get database connection from context data
+    let pool = &ctx.data().database;
+
+    let limit = limit.unwrap_or(10).clamp(1, 25);
+
+    let duplicates = crate::coc::database::get_duplicate_drops(pool, limit).await?;
+
+    if duplicates.is_empty() {
         ctx.send(
             poise::CreateReply::default()
-                .content(format!(
-                    "Building '{}' is already at its maximum level ({})!",
-                    building_name, current_level
-                ))
+                .content("No duplicate drops have been skipped.")
                 .ephemeral(true),
         )
         .await?;
         return Ok(());
     }
 
-    // Step 6: Get upgrade costs using the new enum-based system
-    let target_level = current_level + 1;
-
-    // Step 9: Begin transaction to update resources and building level
-    let mut tx = pool.begin().await?;
+    // Format the results
+    let response = duplicates
+        .iter()
+        .map(|(id, player, source, items, reason, created_at)| {
+            format!(
+                "• **#{}** {} — {} from {} at {}\n  {}",
+                id, player, items, source, created_at, reason
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
 
-    // Upgrade the building
-    sqlx::query!(
-        r#"
-        UPDATE team_buildings
-        SET level = level + 1
-        WHERE id = $1
-        "#,
-        building_id
+    ctx.send(
+        poise::CreateReply::default()
+            .content(fit_message(format!(
+                "**Skipped duplicate drops:**\n{}",
+                response
+            )))
+            .ephemeral(true),
     )
-    .execute(&mut *tx)
     .await?;
 
-    // Commit the transaction
-    tx.commit().await?;
+    Ok(())
+}
 
-    // Step 10: Send success message (public announcement)
-    let building_display_name = building_config.name.clone();
-    let icon = if !building_config.icon.is_empty() {
-        format!("{} ", building_config.icon)
-    } else {
-        String::new()
-    };
+/// Admin Command to Force Upgrade a building for a team
+#[poise::command(slash_command, prefix_command, guild_only, owners_only)]
+pub async fn force_upgrade_building(
+    ctx: Context<'_>,
+    #[description = "Name of the team"] team_name: String,
+    #[description = "Name of the building to upgrade"] building_name: String,
+) -> Result<(), Error> {
+    let actor = Actor::discord(&ctx.author().name);
+    let outcome =
+        admin::force_upgrade_building(ctx.data(), &actor, &team_name, &building_name).await?;
 
-    ctx.say(format!(
-        "{}**{}** upgraded to level **{}** for team **{}**!\n\n**Resources used:**\n None.",
-        icon, building_display_name, target_level, team_name
-    ))
-    .await?;
+    let changed = outcome.is_ok();
+    reply_outcome(ctx, outcome, true).await?;
+    if !changed {
+        return Ok(());
+    }
 
-    // Step 11: Update any team embeds
-    if let Ok((count, _)) =
-        update_team_embeds(&ctx.serenity_context(), &ctx.data(), &team_name).await
-    {
+    let team_name = team_name.to_lowercase();
+    let building_name = building_name.to_lowercase();
+
+    // Update any team embeds
+    if let Ok((count, _)) = update_team_embeds(ctx.http(), ctx.data(), &team_name).await {
         if count > 0 {
             ctx.send(
                 poise::CreateReply::default()
@@ -776,14 +867,10 @@ pub async fn force_upgrade_building(
         }
     }
 
-    // Step 12: Update global embeds if this was a town hall upgrade
+    // Update global embeds if this was a town hall change
     if building_name == "townhall" || building_name == "town_hall" {
-        if let Ok((count, _)) = update_global_embeds(
-            &ctx.serenity_context(),
-            &ctx.data(),
-            Some("townhall_ranking"),
-        )
-        .await
+        if let Ok((count, _)) =
+            update_global_embeds(ctx.http(), ctx.data(), Some("townhall_ranking")).await
         {
             if count > 0 {
                 ctx.send(
@@ -1105,9 +1192,7 @@ pub async fn upgrade_building(
     .await?;
 
     // Step 11: Update any team embeds
-    if let Ok((count, _)) =
-        update_team_embeds(&ctx.serenity_context(), &ctx.data(), &team_name).await
-    {
+    if let Ok((count, _)) = update_team_embeds(ctx.http(), ctx.data(), &team_name).await {
         if count > 0 {
             ctx.send(
                 poise::CreateReply::default()
@@ -1123,12 +1208,8 @@ pub async fn upgrade_building(
 
     // Step 12: Update global embeds if this was a town hall upgrade
     if building_name == "townhall" || building_name == "town_hall" {
-        if let Ok((count, _)) = update_global_embeds(
-            &ctx.serenity_context(),
-            &ctx.data(),
-            Some("townhall_ranking"),
-        )
-        .await
+        if let Ok((count, _)) =
+            update_global_embeds(ctx.http(), &ctx.data(), Some("townhall_ranking")).await
         {
             if count > 0 {
                 ctx.send(
@@ -1151,133 +1232,20 @@ pub async fn downgrade_building(
     #[description = "Name of the team"] team_name: String,
     #[description = "Name of the building to downgrade"] building_name: String,
 ) -> Result<(), Error> {
-    // Get database connection and configs from context data
-    let pool = &ctx.data().database;
-    let town_config = &ctx.data().town_config;
-
-    // Convert inputs to lowercase for consistent lookups
-    let team_name = team_name.to_lowercase();
-    let building_name = building_name.to_lowercase();
-
-    // Step 1: Check if the team exists
-    let team = sqlx::query!(
-        r#"
-        SELECT id as "id: Option<i32>" FROM teams WHERE name = $1
-        "#,
-        team_name
-    )
-    .fetch_optional(pool)
-    .await?;
-
-    let team_id = match team {
-        Some(team) => team.id.ok_or_else(|| Error::from("Team ID is null"))?,
-        None => {
-            ctx.send(
-                poise::CreateReply::default()
-                    .content(format!("No team found with name '{}'", team_name))
-                    .ephemeral(true),
-            )
-            .await?;
-            return Ok(());
-        }
-    };
+    let actor = Actor::discord(&ctx.author().name);
+    let outcome = admin::downgrade_building(ctx.data(), &actor, &team_name, &building_name).await?;
 
-    // Step 2: Check if the building exists in the configuration
-    if !town_config.assets.contains_key(&building_name) {
-        ctx.send(
-            poise::CreateReply::default()
-                .content(format!(
-                    "Building '{}' does not exist in the configuration. Available buildings: {}",
-                    building_name,
-                    town_config.get_building_types().join(", ")
-                ))
-                .ephemeral(true),
-        )
-        .await?;
+    let changed = outcome.is_ok();
+    reply_outcome(ctx, outcome, true).await?;
+    if !changed {
         return Ok(());
     }
 
-    // Step 3: Check if the team has this building and get its current level
-    let building = sqlx::query!(
-        r#"
-        SELECT id as "id: Option<i32>", level FROM team_buildings 
-        WHERE team_id = $1 AND building_name = $2
-        "#,
-        team_id,
-        building_name
-    )
-    .fetch_optional(pool)
-    .await?;
-
-    let (building_id, current_level) = match building {
-        Some(building) => (
-            building
-                .id
-                .ok_or_else(|| Error::from("Building ID is null"))?,
-            building.level,
-        ),
-        None => {
-            ctx.send(
-                poise::CreateReply::default()
-                    .content(format!(
-                        "Team '{}' doesn't have a '{}' building. Please check the building name.",
-                        team_name, building_name
-                    ))
-                    .ephemeral(true),
-            )
-            .await?;
-            return Ok(());
-        }
-    };
-
-    // Step 4: Check if building is at starting level or not built
-    let building_config = &town_config.assets[&building_name];
-    if current_level <= building_config.starting_level as i64 {
-        ctx.send(
-            poise::CreateReply::default()
-                .content(format!(
-                    "Building '{}' is already at its starting level ({}) and cannot be downgraded further!",
-                    building_name, current_level
-                ))
-                .ephemeral(true),
-        )
-        .await?;
-        return Ok(());
-    }
-
-    // Step 5: Calculate the target level
-    let target_level = current_level - 1;
-
-    // Step 6: Downgrade the building
-    sqlx::query!(
-        r#"
-        UPDATE team_buildings
-        SET level = level - 1
-        WHERE id = $1
-        "#,
-        building_id
-    )
-    .execute(pool)
-    .await?;
-
-    // Step 7: Send success message (public announcement)
-    let building_display_name = building_config.name.clone();
-    let icon = if !building_config.icon.is_empty() {
-        format!("{} ", building_config.icon)
-    } else {
-        String::new()
-    };
-
-    ctx.say(format!(
-        "{}**{}** downgraded to level **{}** for team **{}**!",
-        icon, building_display_name, target_level, team_name
-    ))
-    .await?;
+    let team_name = team_name.to_lowercase();
+    let building_name = building_name.to_lowercase();
 
-    // Step 8: Update any team embeds
-    if let Ok((count, _)) =
-        update_team_embeds(&ctx.serenity_context(), &ctx.data(), &team_name).await
-    {
+    // Update any team embeds
+    if let Ok((count, _)) = update_team_embeds(ctx.http(), ctx.data(), &team_name).await {
         if count > 0 {
             ctx.send(
                 poise::CreateReply::default()
@@ -1291,14 +1259,10 @@ pub async fn downgrade_building(
         }
     }
 
-    // Step 12: Update global embeds if this was a town hall upgrade
+    // Update global embeds if this was a town hall change
     if building_name == "townhall" || building_name == "town_hall" {
-        if let Ok((count, _)) = update_global_embeds(
-            &ctx.serenity_context(),
-            &ctx.data(),
-            Some("townhall_ranking"),
-        )
-        .await
+        if let Ok((count, _)) =
+            update_global_embeds(ctx.http(), ctx.data(), Some("townhall_ranking")).await
         {
             if count > 0 {
                 ctx.send(
@@ -1491,7 +1455,7 @@ pub async fn buildings_overview(ctx: Context<'_>) -> Result<(), Error> {
 
 /// Updates all registered global embeds
 pub async fn update_global_embeds(
-    ctx: &serenity::Context,
+    http: &serenity::Http,
     data: &Data,
     variant_filter: Option<&str>,
 ) -> Result<(usize, Vec<String>), Error> {
@@ -1563,7 +1527,7 @@ pub async fn update_global_embeds(
         // Try to edit the message
         let result = channel_id
             .edit_message(
-                &ctx.http,
+                http,
                 message_id,
                 serenity::builder::EditMessage::new().embed(embed),
             )
@@ -1571,6 +1535,7 @@ pub async fn update_global_embeds(
 
         match result {
             Ok(_) => {
+                data.metrics.embed_edited();
                 updated_count += 1;
                 results.push(format!(
                     "Updated global {} embed in channel {}",
@@ -1578,6 +1543,7 @@ pub async fn update_global_embeds(
                 ));
             }
             Err(err) => {
+                data.metrics.discord_error();
                 results.push(format!(
                     "Failed to update global {} embed in channel {}: {}",
                     variant, channel_id, err
@@ -1,5 +1,5 @@
 use crate::{coc::GLOB_MULT, Error};
-use sqlx::SqlitePool;
+use sqlx::{SqliteConnection, SqlitePool};
 
 pub async fn get_user_team(
     pool: &SqlitePool,
@@ -124,60 +124,32 @@ pub async fn get_resource_quantity_by_category(
     }
 }
 
-pub async fn update_resource_quantity(
-    pool: &SqlitePool,
-    team_id: i32,
-    item_name: &str,
-    quantity: i64,
-) -> Result<(), Error> {
-    sqlx::query!(
-        r#"
-        UPDATE resources
-        SET quantity = $1
-        WHERE team_id = $2 AND name = $3
-        "#,
-        quantity,
-        team_id,
-        item_name
-    )
-    .execute(pool)
-    .await?;
-
-    Ok(())
-}
-
-pub async fn insert_new_resource(
-    pool: &SqlitePool,
+/// Adds to a team's resource, creating it if the team doesn't have it yet.
+/// A single upsert, so concurrent credits to one resource can't lose updates
+/// or collide on the new row's id. Returns the new quantity.
+pub async fn add_resource_quantity(
+    conn: &mut SqliteConnection,
     team_id: i32,
     item_name: &str,
     category: &str,
     quantity: i64,
-) -> Result<(), Error> {
-    let max_id_result = sqlx::query!(
-        r#"
-        SELECT MAX(id) as "max_id: i32" FROM resources
-        "#
-    )
-    .fetch_one(pool)
-    .await?;
-
-    let next_id = max_id_result.max_id.unwrap_or(0) + 1;
-
-    sqlx::query!(
+) -> Result<i64, Error> {
+    let record = sqlx::query!(
         r#"
         INSERT INTO resources (team_id, id, quantity, name, category)
-        VALUES ($1, $2, $3, $4, $5)
+        VALUES ($1, (SELECT COALESCE(MAX(id), 0) + 1 FROM resources), $2, $3, $4)
+        ON CONFLICT(team_id, name) DO UPDATE SET quantity = quantity + excluded.quantity
+        RETURNING quantity as "quantity!: i64"
         "#,
         team_id,
-        next_id,
         quantity,
         item_name,
         category
     )
-    .execute(pool)
+    .fetch_one(&mut *conn)
     .await?;
 
-    Ok(())
+    Ok(record.quantity)
 }
 
 pub async fn get_all_teams(pool: &SqlitePool) -> Result<Vec<(i32, String)>, Error> {
@@ -354,6 +326,176 @@ pub async fn insert_team_member(
     Ok(())
 }
 
+/// Find a team member by their confirmed Dink account hash.
+/// Returns (member_id, team_id, team_name, username) if found.
+pub async fn get_member_by_account_hash(
+    pool: &SqlitePool,
+    account_hash: &str,
+) -> Result<Option<(i32, i32, String, String)>, Error> {
+    let result = sqlx::query!(
+        r#"
+        SELECT tm.id as "member_id!: i32", tm.team_id as "team_id: i32", t.name as team_name, tm.username
+        FROM team_members tm
+        JOIN teams t ON tm.team_id = t.id
+        WHERE tm.account_hash = $1
+        "#,
+        account_hash
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(result.map(|r| (r.member_id, r.team_id, r.team_name, r.username)))
+}
+
+/// Find a team member by username.
+/// Returns (member_id, team_id, team_name, account_hash) if found.
+pub async fn get_member_by_username(
+    pool: &SqlitePool,
+    username: &str,
+) -> Result<Option<(i32, i32, String, Option<String>)>, Error> {
+    let result = sqlx::query!(
+        r#"
+        SELECT tm.id as "member_id!: i32", tm.team_id as "team_id: i32", t.name as team_name, tm.account_hash
+        FROM team_members tm
+        JOIN teams t ON tm.team_id = t.id
+        WHERE tm.username = $1
+        "#,
+        username
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(result.map(|r| (r.member_id, r.team_id, r.team_name, r.account_hash)))
+}
+
+pub async fn update_team_member_username(
+    pool: &SqlitePool,
+    member_id: i32,
+    username: &str,
+) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+        UPDATE team_members
+        SET username = $1
+        WHERE id = $2
+        "#,
+        username,
+        member_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Remember the account hash seen for an unbound member until an admin confirms it
+pub async fn set_pending_account_hash(
+    pool: &SqlitePool,
+    member_id: i32,
+    account_hash: &str,
+) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+        UPDATE team_members
+        SET pending_account_hash = $1
+        WHERE id = $2 AND account_hash IS NULL
+        "#,
+        account_hash,
+        member_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Bind a member to their pending account hash.
+/// Returns the confirmed hash, or `None` if there was nothing to confirm.
+pub async fn confirm_account_hash(
+    pool: &SqlitePool,
+    username: &str,
+) -> Result<Option<String>, Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE team_members
+        SET account_hash = pending_account_hash, pending_account_hash = NULL
+        WHERE username = $1 AND pending_account_hash IS NOT NULL
+        RETURNING account_hash as "account_hash!: String"
+        "#,
+        username
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(result.map(|r| r.account_hash))
+}
+
+/// Remove a member's account binding, e.g. after a mistaken confirmation
+pub async fn clear_account_hash(pool: &SqlitePool, username: &str) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE team_members
+        SET account_hash = NULL, pending_account_hash = NULL
+        WHERE username = $1
+        "#,
+        username
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Checks the database answers a trivial query
+pub async fn ping(pool: &SqlitePool) -> Result<(), Error> {
+    sqlx::query!("SELECT 1 as one").fetch_one(pool).await?;
+
+    Ok(())
+}
+
+pub async fn add_clan_guest(pool: &SqlitePool, username: &str) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        r#"
+        INSERT OR IGNORE INTO clan_guests (username)
+        VALUES ($1)
+        "#,
+        username
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn remove_clan_guest(pool: &SqlitePool, username: &str) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM clan_guests
+        WHERE username = $1
+        "#,
+        username
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn is_clan_guest(pool: &SqlitePool, username: &str) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        r#"
+        SELECT username
+        FROM clan_guests
+        WHERE username = $1
+        "#,
+        username
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(result.is_some())
+}
+
 pub async fn delete_team_members(pool: &SqlitePool, username: &str) -> Result<(), Error> {
     sqlx::query!(
         r#"
@@ -389,6 +531,134 @@ pub async fn get_team_resources(
         .collect())
 }
 
+/// Returns each resource a team holds as (name, category, quantity)
+pub async fn get_team_resource_items(
+    pool: &SqlitePool,
+    team_id: i32,
+) -> Result<Vec<(String, String, i64)>, Error> {
+    let resources = sqlx::query!(
+        r#"
+        SELECT name, category, quantity
+        FROM resources
+        WHERE team_id = $1
+        ORDER BY category ASC, name ASC
+        "#,
+        team_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(resources
+        .into_iter()
+        .map(|res| (res.name, res.category, res.quantity))
+        .collect())
+}
+
+/// Returns the usernames of a team's members
+pub async fn get_team_members(pool: &SqlitePool, team_id: i32) -> Result<Vec<String>, Error> {
+    let members = sqlx::query!(
+        r#"
+        SELECT username
+        FROM team_members
+        WHERE team_id = $1
+        ORDER BY username ASC
+        "#,
+        team_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(members.into_iter().map(|member| member.username).collect())
+}
+
+/// Returns each of a team's buildings as (building name, level)
+pub async fn get_team_buildings(
+    pool: &SqlitePool,
+    team_id: i32,
+) -> Result<Vec<(String, i32)>, Error> {
+    let buildings = sqlx::query!(
+        r#"
+        SELECT building_name, level as "level: i32"
+        FROM team_buildings
+        WHERE team_id = $1
+        ORDER BY building_name ASC
+        "#,
+        team_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(buildings
+        .into_iter()
+        .map(|building| (building.building_name, building.level))
+        .collect())
+}
+
+/// Returns the multipliers a team's buildings currently grant as
+/// (building name, resource category, multiplier, flat bonus)
+pub async fn get_team_multipliers(
+    pool: &SqlitePool,
+    team_id: i32,
+) -> Result<Vec<(String, String, f64, i64)>, Error> {
+    let multipliers = sqlx::query!(
+        r#"
+        SELECT
+            building_name as "building_name!: String",
+            resource_category as "resource_category!: String",
+            multiplier as "multiplier!: f64",
+            flat_bonus as "flat_bonus!: i64"
+        FROM team_resource_multipliers
+        WHERE team_id = $1
+        ORDER BY building_name ASC, resource_category ASC
+        "#,
+        team_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(multipliers
+        .into_iter()
+        .map(|m| {
+            (
+                m.building_name,
+                m.resource_category,
+                m.multiplier,
+                m.flat_bonus,
+            )
+        })
+        .collect())
+}
+
+/// Returns every team as (team name, town hall level, total resources),
+/// ranked by town hall level then total resources
+pub async fn get_leaderboard(pool: &SqlitePool) -> Result<Vec<(String, i32, i64)>, Error> {
+    let teams = sqlx::query!(
+        r#"
+        SELECT
+            t.name as "team_name!: String",
+            COALESCE(
+                (SELECT MAX(tb.level) FROM team_buildings tb
+                 WHERE tb.team_id = t.id
+                 AND LOWER(tb.building_name) IN ('townhall', 'town_hall')),
+                0
+            ) as "town_hall_level!: i32",
+            COALESCE(
+                (SELECT SUM(r.quantity) FROM resources r WHERE r.team_id = t.id),
+                0
+            ) as "total_resources!: i64"
+        FROM teams t
+        ORDER BY 2 DESC, 3 DESC, t.name ASC
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(teams
+        .into_iter()
+        .map(|team| (team.team_name, team.town_hall_level, team.total_resources))
+        .collect())
+}
+
 pub async fn get_team_embeds(
     pool: &SqlitePool,
     team_id: i32,
@@ -554,6 +824,37 @@ pub async fn get_team_resource_flat_bonus(
     Ok(result.bonus)
 }
 
+/// How a raw drop quantity became the amount credited to a team
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceCalculation {
+    pub base: i32,
+    pub multiplier: f64,
+    pub flat_bonus: i32,
+    pub handicap: f64,
+    pub global_multiplier: f64,
+    pub total: i32,
+}
+
+impl ResourceCalculation {
+    /// Apply multiplier first, then add flat bonus, then scale by handicap and GLOB_MULT:
+    /// ceil((floor(base * multiplier) + flat_bonus) * handicap * GLOB_MULT)
+    pub fn new(base: i32, multiplier: f64, flat_bonus: i32, handicap: f64) -> Self {
+        let total = (((base as f64 * multiplier).floor() + flat_bonus as f64)
+            * handicap
+            * GLOB_MULT)
+            .ceil() as i32;
+
+        Self {
+            base,
+            multiplier,
+            flat_bonus,
+            handicap,
+            global_multiplier: GLOB_MULT,
+            total,
+        }
+    }
+}
+
 /// Calculate total resources after applying multiplier and bonus
 pub async fn calculate_resource_total(
     pool: &SqlitePool,
@@ -561,23 +862,38 @@ pub async fn calculate_resource_total(
     team_id: i32,
     resource_category: &str,
 ) -> Result<i32, Error> {
+    Ok(
+        calculate_resource_breakdown(pool, base_amount, team_id, resource_category)
+            .await?
+            .total,
+    )
+}
+
+/// Work out the credited amount of a resource along with the bonuses that produced it
+pub async fn calculate_resource_breakdown(
+    pool: &SqlitePool,
+    base_amount: i32,
+    team_id: i32,
+    resource_category: &str,
+) -> Result<ResourceCalculation, Error> {
     let mult = get_team_resource_multiplier(pool, team_id, resource_category).await?;
     let flat_bonus = get_team_resource_flat_bonus(pool, team_id, resource_category).await?;
     let handicap = get_team_handicap_multiplier(pool, team_id).await?;
     // let handicap = 1.0; // Disable handicap for now
 
-    // Apply multiplier first, then add flat bonus: floor(base_amount * multiplier) + flat_bonus
-    let base = base_amount as f64;
-    let flat = flat_bonus as f64;
+    let calculation = ResourceCalculation::new(base_amount, mult, flat_bonus, handicap);
 
     println!(
-        "Resource calculation: base={}, mult={:.2}, flat={:.2}, handicap={:.2}, GLOB_MULT={:.2}, total={}",
-        base, mult, flat, handicap, GLOB_MULT, (((base * mult).floor() + flat) * handicap * GLOB_MULT).floor() as i32
+        "Resource calculation: base={}, mult={:.2}, flat={}, handicap={:.2}, GLOB_MULT={:.2}, total={}",
+        calculation.base,
+        calculation.multiplier,
+        calculation.flat_bonus,
+        calculation.handicap,
+        calculation.global_multiplier,
+        calculation.total
     );
 
-    let total = (((base * mult).floor() + flat) * handicap * GLOB_MULT).ceil() as i32;
-
-    Ok(total)
+    Ok(calculation)
 }
 
 /// Get the level of a specific building for a team
@@ -730,3 +1046,649 @@ pub async fn get_all_global_embeds(
 
     Ok(result)
 }
+
+/// Records a drop fingerprint unless the same fingerprint was already seen
+/// within `window_secs` of `dropped_at`.
+/// Returns the new fingerprint ID, or `None` if the drop is a duplicate.
+pub async fn insert_drop_fingerprint(
+    pool: &SqlitePool,
+    fingerprint: &str,
+    player: &str,
+    source: &str,
+    dropped_at: i64,
+    window_secs: i64,
+) -> Result<Option<i64>, Error> {
+    // Check and insert in a single statement so concurrent ingest paths
+    // can't both record the same drop
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO drop_fingerprints (fingerprint, player, source, dropped_at)
+        SELECT $1, $2, $3, $4
+        WHERE NOT EXISTS (
+            SELECT 1 FROM drop_fingerprints
+            WHERE fingerprint = $1 AND ABS(dropped_at - $4) <= $5
+        )
+        RETURNING id as "id!: i64"
+        "#,
+        fingerprint,
+        player,
+        source,
+        dropped_at,
+        window_secs
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(result.map(|record| record.id))
+}
+
+/// Finds the earlier drop a duplicate was matched against.
+/// Returns (fingerprint_id, dropped_at) if found.
+pub async fn get_drop_fingerprint(
+    pool: &SqlitePool,
+    fingerprint: &str,
+    dropped_at: i64,
+    window_secs: i64,
+) -> Result<Option<(i64, i64)>, Error> {
+    let result = sqlx::query!(
+        r#"
+        SELECT id as "id!: i64", dropped_at
+        FROM drop_fingerprints
+        WHERE fingerprint = $1 AND ABS(dropped_at - $2) <= $3
+        ORDER BY id ASC
+        LIMIT 1
+        "#,
+        fingerprint,
+        dropped_at,
+        window_secs
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(result.map(|record| (record.id, record.dropped_at)))
+}
+
+/// Removes a fingerprint so a drop that failed to process can be retried
+pub async fn delete_drop_fingerprint(pool: &SqlitePool, fingerprint_id: i64) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM drop_fingerprints WHERE id = $1
+        "#,
+        fingerprint_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn insert_duplicate_drop(
+    pool: &SqlitePool,
+    fingerprint: &str,
+    original_id: Option<i64>,
+    player: &str,
+    source: &str,
+    items: &str,
+    reason: &str,
+    dropped_at: i64,
+) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO duplicate_drops (fingerprint, original_id, player, source, items, reason, dropped_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        fingerprint,
+        original_id,
+        player,
+        source,
+        items,
+        reason,
+        dropped_at
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Get the most recent skipped duplicates.
+/// Returns (id, player, source, items, reason, created_at).
+pub async fn get_duplicate_drops(
+    pool: &SqlitePool,
+    limit: i64,
+) -> Result<Vec<(i64, String, String, String, String, String)>, Error> {
+    let records = sqlx::query!(
+        r#"
+        SELECT
+            id as "id!: i64",
+            player,
+            source,
+            items,
+            reason,
+            created_at as "created_at!: String"
+        FROM duplicate_drops
+        ORDER BY id DESC
+        LIMIT $1
+        "#,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(records
+        .into_iter()
+        .map(|r| (r.id, r.player, r.source, r.items, r.reason, r.created_at))
+        .collect())
+}
+
+pub async fn insert_admin_audit(
+    pool: &SqlitePool,
+    source: &str,
+    actor: &str,
+    action: &str,
+    target: &str,
+    details: Option<&str>,
+    outcome: &str,
+) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO admin_audit (source, actor, action, target, details, outcome)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        source,
+        actor,
+        action,
+        target,
+        details,
+        outcome
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// One credited item of a drop, as recorded in the drop ledger
+pub struct LedgerEntry<'a> {
+    pub drop_id: Option<i64>,
+    pub player: &'a str,
+    pub team_id: i32,
+    pub source: &'a str,
+    pub item_name: &'a str,
+    pub category: &'a str,
+    pub raw_quantity: i64,
+    pub calculation: &'a ResourceCalculation,
+    pub ingest_path: &'a str,
+    pub message_id: Option<i64>,
+    pub dropped_at: i64,
+}
+
+async fn insert_drop_ledger(
+    conn: &mut SqliteConnection,
+    entry: &LedgerEntry<'_>,
+) -> Result<(), Error> {
+    let credited_quantity = entry.calculation.total as i64;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO drop_ledger (
+            drop_id, player, team_id, source, item_name, category, raw_quantity,
+            credited_quantity, multiplier, flat_bonus, handicap, global_multiplier,
+            ingest_path, message_id, dropped_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+        "#,
+        entry.drop_id,
+        entry.player,
+        entry.team_id,
+        entry.source,
+        entry.item_name,
+        entry.category,
+        entry.raw_quantity,
+        credited_quantity,
+        entry.calculation.multiplier,
+        entry.calculation.flat_bonus,
+        entry.calculation.handicap,
+        entry.calculation.global_multiplier,
+        entry.ingest_path,
+        entry.message_id,
+        entry.dropped_at
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Credits the items of a drop and records them in the ledger, all in one
+/// transaction so a drop is either fully credited or not at all
+pub async fn credit_drop_items(
+    pool: &SqlitePool,
+    entries: &[LedgerEntry<'_>],
+) -> Result<(), Error> {
+    let mut tx = pool.begin().await?;
+
+    for entry in entries {
+        add_resource_quantity(
+            &mut tx,
+            entry.team_id,
+            entry.item_name,
+            entry.category,
+            entry.calculation.total as i64,
+        )
+        .await?;
+        insert_drop_ledger(&mut tx, entry).await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Get the credited drops read from a Dink message
+pub async fn get_ledger_drops_by_message(
+    pool: &SqlitePool,
+    message_id: i64,
+) -> Result<Vec<i64>, Error> {
+    let records = sqlx::query!(
+        r#"
+        SELECT DISTINCT drop_id as "drop_id!: i64"
+        FROM drop_ledger
+        WHERE message_id = $1 AND drop_id IS NOT NULL AND reverted_at IS NULL
+        "#,
+        message_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(records.into_iter().map(|r| r.drop_id).collect())
+}
+
+/// Check whether a drop has any ledger entries, reverted or not
+pub async fn drop_ledger_exists(pool: &SqlitePool, drop_id: i64) -> Result<bool, Error> {
+    let record = sqlx::query!(
+        r#"
+        SELECT COUNT(*) as "count!: i64"
+        FROM drop_ledger
+        WHERE drop_id = $1
+        "#,
+        drop_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(record.count > 0)
+}
+
+/// Takes back everything a drop credited and marks its ledger entries as reverted.
+/// Quantities never go below zero, as resources may have been spent since.
+/// Returns (team_name, player, source, item_name, credited, removed) per entry.
+pub async fn revert_drop_ledger(
+    pool: &SqlitePool,
+    drop_id: i64,
+    reverted_by: &str,
+) -> Result<Vec<(String, String, String, String, i64, i64)>, Error> {
+    let mut tx = pool.begin().await?;
+
+    let entries = sqlx::query!(
+        r#"
+        SELECT
+            l.team_id as "team_id!: i32",
+            t.name as "team_name!: String",
+            l.player,
+            l.source,
+            l.item_name,
+            l.credited_quantity as "credited_quantity!: i64"
+        FROM drop_ledger l
+        JOIN teams t ON t.id = l.team_id
+        WHERE l.drop_id = $1 AND l.reverted_at IS NULL
+        ORDER BY l.id
+        "#,
+        drop_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut reverted = Vec::new();
+    for entry in entries {
+        let current = sqlx::query!(
+            r#"
+            SELECT quantity as "quantity!: i64"
+            FROM resources
+            WHERE team_id = $1 AND name = $2
+            "#,
+            entry.team_id,
+            entry.item_name
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .map(|r| r.quantity)
+        .unwrap_or(0);
+
+        let removed = entry.credited_quantity.min(current).max(0);
+
+        sqlx::query!(
+            r#"
+            UPDATE resources
+            SET quantity = quantity - $1
+            WHERE team_id = $2 AND name = $3
+            "#,
+            removed,
+            entry.team_id,
+            entry.item_name
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        reverted.push((
+            entry.team_name,
+            entry.player,
+            entry.source,
+            entry.item_name,
+            entry.credited_quantity,
+            removed,
+        ));
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE drop_ledger
+        SET reverted_at = CURRENT_TIMESTAMP, reverted_by = $1
+        WHERE drop_id = $2 AND reverted_at IS NULL
+        "#,
+        reverted_by,
+        drop_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(reverted)
+}
+
+/// A rejected drop, as recorded in the rejection log
+pub struct Rejection<'a> {
+    pub player: &'a str,
+    pub team_name: Option<&'a str>,
+    pub source: &'a str,
+    pub reason_code: &'a str,
+    pub reason: &'a str,
+    pub items: &'a str,
+    pub ingest_path: &'a str,
+    pub queue_id: Option<i64>,
+    pub message_id: Option<i64>,
+    pub dropped_at: i64,
+}
+
+pub async fn insert_drop_rejection(
+    pool: &SqlitePool,
+    rejection: &Rejection<'_>,
+) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO drop_rejections (
+            player, team_name, source, reason_code, reason, items, ingest_path,
+            queue_id, message_id, dropped_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        "#,
+        rejection.player,
+        rejection.team_name,
+        rejection.source,
+        rejection.reason_code,
+        rejection.reason,
+        rejection.items,
+        rejection.ingest_path,
+        rejection.queue_id,
+        rejection.message_id,
+        rejection.dropped_at
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Get the most recent rejected drops, optionally filtered by reason code,
+/// player and age in hours.
+/// Returns (id, player, team_name, source, reason, items, payload reference, created_at).
+pub async fn get_drop_rejections(
+    pool: &SqlitePool,
+    reason_code: Option<&str>,
+    player: Option<&str>,
+    hours: Option<i64>,
+    limit: i64,
+) -> Result<
+    Vec<(
+        i64,
+        String,
+        Option<String>,
+        String,
+        String,
+        String,
+        String,
+        String,
+    )>,
+    Error,
+> {
+    let since = hours.map(|hours| format!("-{} hours", hours));
+
+    let records = sqlx::query!(
+        r#"
+        SELECT
+            id as "id!: i64",
+            player,
+            team_name,
+            source,
+            reason,
+            items,
+            ingest_path,
+            queue_id as "queue_id: i64",
+            message_id as "message_id: i64",
+            created_at as "created_at!: String"
+        FROM drop_rejections
+        WHERE ($1 IS NULL OR reason_code = $1)
+            AND ($2 IS NULL OR LOWER(player) = LOWER($2))
+            AND ($3 IS NULL OR created_at >= datetime('now', $3))
+        ORDER BY id DESC
+        LIMIT $4
+        "#,
+        reason_code,
+        player,
+        since,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(records
+        .into_iter()
+        .map(|r| {
+            let reference = match (r.queue_id, r.message_id) {
+                (Some(queue_id), _) => format!("{} payload #{}", r.ingest_path, queue_id),
+                (None, Some(message_id)) => format!("{} message {}", r.ingest_path, message_id),
+                (None, None) => r.ingest_path,
+            };
+            (
+                r.id,
+                r.player,
+                r.team_name,
+                r.source,
+                r.reason,
+                r.items,
+                reference,
+                r.created_at,
+            )
+        })
+        .collect())
+}
+
+/// Get the event window as (starts_at, ends_at), if one was set
+pub async fn get_event_window(
+    pool: &SqlitePool,
+) -> Result<Option<(Option<i64>, Option<i64>)>, Error> {
+    let record = sqlx::query!(
+        r#"
+        SELECT starts_at as "starts_at: i64", ends_at as "ends_at: i64"
+        FROM event_schedule
+        WHERE id = 1
+        "#
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(record.map(|r| (r.starts_at, r.ends_at)))
+}
+
+pub async fn set_event_window(
+    pool: &SqlitePool,
+    starts_at: Option<i64>,
+    ends_at: Option<i64>,
+) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO event_schedule (id, starts_at, ends_at)
+        VALUES (1, $1, $2)
+        ON CONFLICT(id) DO UPDATE SET
+            starts_at = excluded.starts_at,
+            ends_at = excluded.ends_at,
+            updated_at = CURRENT_TIMESTAMP
+        "#,
+        starts_at,
+        ends_at
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Get every pause of the event as (paused_at, resumed_at)
+pub async fn get_event_pauses(pool: &SqlitePool) -> Result<Vec<(i64, Option<i64>)>, Error> {
+    let records = sqlx::query!(
+        r#"
+        SELECT paused_at as "paused_at!: i64", resumed_at as "resumed_at: i64"
+        FROM event_pauses
+        ORDER BY paused_at
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(records
+        .into_iter()
+        .map(|r| (r.paused_at, r.resumed_at))
+        .collect())
+}
+
+/// Pause the event, returns false if it is already paused
+pub async fn start_event_pause(pool: &SqlitePool, paused_at: i64) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO event_pauses (paused_at)
+        SELECT $1
+        WHERE NOT EXISTS (SELECT 1 FROM event_pauses WHERE resumed_at IS NULL)
+        "#,
+        paused_at
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Resume the event, returns false if it wasn't paused
+pub async fn end_event_pause(pool: &SqlitePool, resumed_at: i64) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE event_pauses
+        SET resumed_at = $1
+        WHERE resumed_at IS NULL
+        "#,
+        resumed_at
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_drop_credits() {
+        let path = std::env::temp_dir().join(format!("credit-test-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let options = SqliteConnectOptions::new()
+            .filename(&path)
+            .create_if_missing(true)
+            .busy_timeout(std::time::Duration::from_secs(30));
+        let pool = SqlitePoolOptions::new()
+            .max_connections(8)
+            .connect_with(options)
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+
+        sqlx::query("INSERT INTO teams (id, name) VALUES (1, 'hammered')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // Every drop credits the same resource, plus one nobody has yet
+        const DROPS: i64 = 50;
+        let tasks: Vec<_> = (0..DROPS)
+            .map(|n| {
+                let pool = pool.clone();
+                tokio::spawn(async move {
+                    let calculation = ResourceCalculation::new(3, 1.0, 0, 1.0);
+                    let new_item = format!("item {}", n);
+                    let entry = |item_name| LedgerEntry {
+                        drop_id: None,
+                        player: "solo h",
+                        team_id: 1,
+                        source: "Man",
+                        item_name,
+                        category: "misc",
+                        raw_quantity: 3,
+                        calculation: &calculation,
+                        ingest_path: "webhook",
+                        message_id: None,
+                        dropped_at: 0,
+                    };
+
+                    credit_drop_items(&pool, &[entry("coins"), entry(&new_item)])
+                        .await
+                        .unwrap();
+                })
+            })
+            .collect();
+
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        let coins = get_resource_quantity_by_name(&pool, 1, "coins")
+            .await
+            .unwrap();
+        assert_eq!(coins, Some(DROPS * 3));
+
+        let (resources, ledger): (i64, i64) = sqlx::query_as(
+            "SELECT (SELECT COUNT(*) FROM resources WHERE team_id = 1), (SELECT COUNT(*) FROM drop_ledger)",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(resources, DROPS + 1);
+        assert_eq!(ledger, DROPS * 2);
+
+        pool.close().await;
+        let _ = std::fs::remove_file(&path);
+    }
+}
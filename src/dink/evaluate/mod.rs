@@ -0,0 +1,242 @@
+use std::fmt::Write;
+
+use crate::coc::buildings::SourceAccess;
+use crate::coc::database::{self, ResourceCalculation};
+use crate::coc::patterns;
+use crate::{Data, Error};
+
+use super::schedule::EventSchedule;
+use super::{DinkDrop, RejectReason};
+
+/// Whether a team may credit drops from a source, and why
+pub struct SourceCheck<'a> {
+    pub combat_level: Option<u32>,
+    pub slayer_level: Option<u32>,
+    /// The source's access rule, for sources outside the bestiary
+    pub access: Option<&'a SourceAccess>,
+    /// Each gate checked, with whether it passed
+    pub gates: Vec<(String, bool)>,
+    pub result: Result<(), RejectReason>,
+}
+
+/// Checks the team's access to a drop source, without writing anything.
+///
+/// Bestiary monsters are gated by the armory's combat level and the slayer
+/// master's slayer level. Other sources need an entry in `source_access`.
+pub async fn check_source<'a>(
+    data: &'a Data,
+    team_id: i32,
+    source: &str,
+) -> Result<SourceCheck<'a>, Error> {
+    let pool = &data.database;
+
+    let mut check = SourceCheck {
+        combat_level: data.bestiary.get_combat_level(source),
+        slayer_level: data.bestiary.get_slayer_level(source),
+        access: None,
+        gates: Vec::new(),
+        result: Ok(()),
+    };
+
+    if let Some(level) = check.combat_level {
+        let allowed = database::get_team_armory_level(pool, level as i32, team_id)
+            .await?
+            .unwrap_or(false);
+        check
+            .gates
+            .push((format!("Armory allows combat level {}", level), allowed));
+        if !allowed {
+            check.result = Err(RejectReason::CombatLevel(level));
+            return Ok(check);
+        }
+
+        if let Some(level) = check.slayer_level {
+            let allowed = database::get_team_slayer_level(pool, level as i32, team_id)
+                .await?
+                .unwrap_or(false);
+            check.gates.push((
+                format!("Slayer Master allows slayer level {}", level),
+                allowed,
+            ));
+            if !allowed {
+                check.result = Err(RejectReason::SlayerLevel(level));
+            }
+        }
+
+        return Ok(check);
+    }
+
+    let Some(access) = data.town_config.get_source_access(source) else {
+        check.gates.push((
+            "Source is in the bestiary or source_access".to_string(),
+            false,
+        ));
+        check.result = Err(RejectReason::InvalidSource);
+        return Ok(check);
+    };
+    check.access = Some(access);
+
+    match &access.building {
+        Some(building) => {
+            let level = database::get_team_building_level(pool, team_id, building).await?;
+            let allowed = level >= access.min_level as i32;
+            check.gates.push((
+                format!(
+                    "{} level {} unlocks {} (team has level {})",
+                    building, access.min_level, access.source, level
+                ),
+                allowed,
+            ));
+            if !allowed {
+                check.result = Err(RejectReason::Building {
+                    source: access.source.clone(),
+                    building: building.clone(),
+                    level: access.min_level,
+                });
+            }
+        }
+        None => check
+            .gates
+            .push((format!("{} is always allowed", access.source), true)),
+    }
+
+    if !access.categories.is_empty() {
+        check.gates.push((
+            format!("Only credits {}", access.categories.join(", ")),
+            true,
+        ));
+    }
+
+    Ok(check)
+}
+
+/// How one item of a drop would be credited
+pub struct ItemEvaluation {
+    pub name: String,
+    pub quantity: u32,
+    /// Resource category from the matching `resource_pattern`
+    pub category: Option<String>,
+    /// The credited amount, or `None` when the item isn't credited
+    pub calculation: Option<ResourceCalculation>,
+}
+
+/// Works out whether and how much of an item a team would be credited
+pub async fn evaluate_item(
+    data: &Data,
+    team_id: i32,
+    access: Option<&SourceAccess>,
+    item_name: &str,
+    quantity: u32,
+) -> Result<ItemEvaluation, Error> {
+    let name = item_name.to_lowercase();
+    let resource_patterns = &data.res_patterns.resource_pattern;
+
+    let mut item = ItemEvaluation {
+        name,
+        quantity,
+        category: None,
+        calculation: None,
+    };
+
+    if !patterns::matches_pattern(&item.name, resource_patterns) {
+        return Ok(item);
+    }
+
+    let category = patterns::get_resource_category(&item.name, resource_patterns);
+    if access.is_none_or(|access| access.allows_category(&category)) {
+        item.calculation = Some(
+            database::calculate_resource_breakdown(
+                &data.database,
+                quantity as i32,
+                team_id,
+                &category,
+            )
+            .await?,
+        );
+    }
+    item.category = Some(category);
+
+    Ok(item)
+}
+
+/// Runs a drop through the `process_drop` decision path without writing
+/// anything, and describes each step for the player
+pub async fn explain_drop(data: &Data, drop: &DinkDrop) -> Result<String, Error> {
+    let mut report = format!(
+        "**{}** from **{}**\n",
+        drop.user,
+        match drop.source.is_empty() {
+            true => "an unknown source",
+            false => &drop.source,
+        }
+    );
+
+    let schedule = EventSchedule::load(&data.database).await?;
+    if let Err(reason) = schedule.check(drop.timestamp.timestamp()) {
+        writeln!(report, "**Not credited:** {}", reason)?;
+        return Ok(report);
+    }
+
+    let team = match super::find_team(&data.database, drop).await? {
+        Ok(found) => found,
+        Err(reason) => {
+            writeln!(report, "Team: none\n**Not credited:** {}", reason)?;
+            return Ok(report);
+        }
+    };
+    writeln!(report, "Team: **{}**", team.team_name)?;
+
+    let check = check_source(data, team.team_id, &drop.source).await?;
+    writeln!(
+        report,
+        "Bestiary: combat level {}, slayer level {}",
+        check
+            .combat_level
+            .map_or("not found".to_string(), |level| level.to_string()),
+        check
+            .slayer_level
+            .map_or("none".to_string(), |level| level.to_string()),
+    )?;
+
+    for (gate, passed) in &check.gates {
+        writeln!(report, "{} {}", if *passed { "✅" } else { "❌" }, gate)?;
+    }
+
+    if let Err(reason) = &check.result {
+        writeln!(report, "**Not credited:** {}", reason)?;
+        return Ok(report);
+    }
+
+    report.push_str("\n**Items:**\n");
+    for (item_name, quantity) in &drop.loots {
+        let item = evaluate_item(data, team.team_id, check.access, item_name, *quantity).await?;
+
+        match (&item.category, &item.calculation) {
+            (None, _) => writeln!(
+                report,
+                "• {} x {}: not a resource, matches no resource_pattern",
+                item.quantity, item.name
+            )?,
+            (Some(category), None) => writeln!(
+                report,
+                "• {} x {} ({}): not credited from this source",
+                item.quantity, item.name, category
+            )?,
+            (Some(category), Some(calc)) => writeln!(
+                report,
+                "• {} x {} ({}): ⌈(⌊{} × {:.2}⌋ + {}) × {:.2} handicap × {:.2} GLOB_MULT⌉ = **{}**",
+                item.quantity,
+                item.name,
+                category,
+                calc.base,
+                calc.multiplier,
+                calc.flat_bonus,
+                calc.handicap,
+                calc.global_multiplier,
+                calc.total
+            )?,
+        }
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,148 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::dink::{DinkDrop, DropOutcome};
+
+/// Number of events kept for subscribers that fall behind
+pub const EVENT_CAPACITY: usize = 100;
+
+/// Number of recent events kept for the dashboard
+pub const RECENT_CAPACITY: usize = 50;
+
+/// An item in a drop, with the amount credited after multipliers
+#[derive(Debug, Clone, Serialize)]
+pub struct DropEventItem {
+    pub name: String,
+    pub quantity: u32,
+    pub credited: i64,
+}
+
+/// A processed drop, published to live subscribers such as stream overlays
+#[derive(Debug, Clone, Serialize)]
+pub struct DropEvent {
+    pub player: String,
+    pub team: Option<String>,
+    pub source: String,
+    pub items: Vec<DropEventItem>,
+    pub accepted: bool,
+    pub reason: Option<String>,
+    pub message: Option<String>,
+    pub timestamp: String,
+}
+
+impl DropEvent {
+    /// Starts an event for a drop, with nothing credited yet
+    pub fn new(drop: &DinkDrop) -> Self {
+        DropEvent {
+            player: drop.user.clone(),
+            team: None,
+            source: drop.source.clone(),
+            items: drop
+                .loots
+                .iter()
+                .map(|(name, quantity)| DropEventItem {
+                    name: name.clone(),
+                    quantity: *quantity,
+                    credited: 0,
+                })
+                .collect(),
+            accepted: false,
+            reason: None,
+            message: None,
+            timestamp: drop.timestamp.to_rfc3339(),
+        }
+    }
+
+    /// Records the outcome of the drop
+    pub fn finish(mut self, outcome: &DropOutcome) -> Self {
+        match outcome {
+            DropOutcome::Credited => self.accepted = true,
+            DropOutcome::Rejected(reason) => {
+                self.accepted = false;
+                self.reason = Some(reason.code().to_string());
+                self.message = Some(reason.to_string());
+            }
+        }
+        self
+    }
+}
+
+/// Publishes drop events to live subscribers and keeps the most recent ones
+pub struct DropFeed {
+    sender: broadcast::Sender<DropEvent>,
+    recent: Mutex<VecDeque<DropEvent>>,
+}
+
+impl DropFeed {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CAPACITY);
+        DropFeed {
+            sender,
+            recent: Mutex::new(VecDeque::with_capacity(RECENT_CAPACITY)),
+        }
+    }
+
+    /// Publishes an event; live subscribers miss it if none are connected
+    pub fn publish(&self, event: DropEvent) {
+        {
+            let mut recent = self.recent.lock().unwrap();
+            if recent.len() == RECENT_CAPACITY {
+                recent.pop_back();
+            }
+            recent.push_front(event.clone());
+        }
+
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<DropEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Returns the most recent events, newest first
+    pub fn recent(&self) -> Vec<DropEvent> {
+        self.recent.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+impl Default for DropFeed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dink::RejectReason;
+
+    #[test]
+    fn test_drop_event() {
+        let drop = DinkDrop::new(
+            "Zezima".to_string(),
+            "Vorkath".to_string(),
+            vec![("Dragon bones".to_string(), 2)],
+        );
+
+        let feed = DropFeed::new();
+        let mut receiver = feed.subscribe();
+
+        let outcome = DropOutcome::Rejected(RejectReason::NoTeam);
+        feed.publish(DropEvent::new(&drop).finish(&outcome));
+        feed.publish(DropEvent::new(&drop).finish(&DropOutcome::Credited));
+
+        let event = receiver.try_recv().unwrap();
+        assert_eq!(event.player, "Zezima");
+        assert_eq!(event.items[0].quantity, 2);
+        assert_eq!(event.items[0].credited, 0);
+        assert!(!event.accepted);
+        assert_eq!(event.reason.as_deref(), Some("no_team"));
+
+        let recent = feed.recent();
+        assert_eq!(recent.len(), 2);
+        assert!(recent[0].accepted);
+    }
+}
@@ -1,13 +1,23 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
 use poise::serenity_prelude as serenity;
 use reqwest::Client;
+use sha2::{Digest, Sha256};
 
 use crate::coc::commands::update_team_embeds;
-use crate::coc::database::{
-    get_resource_quantity_by_name, get_team_armory_level, get_team_slayer_level, get_user_team,
-    insert_new_resource, update_resource_quantity,
-};
 use crate::coc::{self, database};
 use crate::{Data, Error};
+use events::DropEvent;
+use notification::{ClueExtra, LootExtra, LootItem};
+
+pub mod evaluate;
+pub mod events;
+pub mod notification;
+pub mod parser;
+pub mod policy;
+pub mod schedule;
+pub mod worker;
 
 #[cfg(test)]
 mod tests {
@@ -17,7 +27,9 @@ mod tests {
     fn test_parse_loot_text() {
         let input = "Solo H has looted: \n\n1 x [Bones](https://oldschool.runescape.wiki/w/Special:Search?search=Bones) (62)\n15 x [Coins](https://oldschool.runescape.wiki/w/Special:Search?search=Coins) (15)\nFrom: [Man](https://oldschool.runescape.wiki/w/Special:Search?search=Man)";
 
-        let dink_drop = parse_loot_text(input).unwrap();
+        let dink_drop = parser::parse_loot_embed(None, input, &[])
+            .unwrap()
+            .remove(0);
 
         assert_eq!(dink_drop.user, "Solo H");
         assert_eq!(
@@ -26,12 +38,212 @@ mod tests {
         );
         assert_eq!(dink_drop.source, "Man");
     }
+
+    #[test]
+    fn test_drop_from_loot_extra() {
+        let extra = LootExtra {
+            items: vec![
+                LootItem {
+                    id: 526,
+                    name: "Bones".to_string(),
+                    quantity: 1,
+                    price_each: 62,
+                },
+                LootItem {
+                    id: 995,
+                    name: "Coins".to_string(),
+                    quantity: 15,
+                    price_each: 1,
+                },
+            ],
+            source: "Man".to_string(),
+            category: Some("NPC".to_string()),
+            kill_count: None,
+        };
+
+        let dink_drop = DinkDrop::from_loot_extra("Solo H", &extra);
+
+        assert_eq!(dink_drop.user, "Solo H");
+        assert_eq!(dink_drop.source, "Man");
+        assert_eq!(
+            dink_drop.loots,
+            vec![("Bones".to_string(), 1), ("Coins".to_string(), 15)]
+        );
+        assert_eq!(dink_drop.items[0].id, 526);
+        assert_eq!(dink_drop.total_value(), 77);
+    }
+
+    #[test]
+    fn test_reject_reason_codes() {
+        let reasons = [
+            RejectReason::NoTeam,
+            RejectReason::CombatLevel(100),
+            RejectReason::SlayerLevel(85),
+            RejectReason::Building {
+                source: "Theatre of Blood".to_string(),
+                building: "garrisons".to_string(),
+                level: 6,
+            },
+            RejectReason::InvalidSource,
+            RejectReason::Duplicate,
+            RejectReason::AccountMismatch,
+            RejectReason::SeasonalWorld,
+            RejectReason::World(302),
+            RejectReason::Region(12850),
+            RejectReason::Clan(String::new()),
+            RejectReason::EventNotStarted(0),
+            RejectReason::EventEnded(0),
+            RejectReason::EventPaused,
+        ];
+
+        let codes: Vec<&str> = reasons.iter().map(RejectReason::code).collect();
+        assert_eq!(codes, RejectReason::CODES);
+    }
+
+    #[test]
+    fn test_drop_fingerprint() {
+        let drop = DinkDrop::new(
+            "Solo H".to_string(),
+            "Man".to_string(),
+            vec![("Bones".to_string(), 1), ("Coins".to_string(), 15)],
+        );
+
+        // Item order and name casing don't matter
+        let reordered = DinkDrop::new(
+            "solo h".to_string(),
+            "man".to_string(),
+            vec![("Coins".to_string(), 15), ("bones".to_string(), 1)],
+        );
+        assert_eq!(drop.fingerprint("solo h"), reordered.fingerprint("solo h"));
+
+        let other_source = DinkDrop::new(
+            "Solo H".to_string(),
+            "Woman".to_string(),
+            vec![("Bones".to_string(), 1), ("Coins".to_string(), 15)],
+        );
+        assert_ne!(
+            drop.fingerprint("solo h"),
+            other_source.fingerprint("solo h")
+        );
+        assert_ne!(drop.fingerprint("solo h"), drop.fingerprint("abc123"));
+        assert_eq!(drop.items_summary(), "1 x Bones, 15 x Coins");
+
+        // Stored fingerprints must keep matching after a rebuild
+        assert_eq!(
+            drop.fingerprint("solo h"),
+            "2ec86e79ee8d0994afedcf9b61871688aef2f44a2bfdf92a8c14da6fe0b40f33"
+        );
+    }
+
+    #[test]
+    fn test_webhook_message() {
+        let credited = webhook_message("Solo H", true, "Man", Some(&credited_message(42)));
+        assert_eq!(credited, "Solo H Man ✅ Drop #42 processed successfully");
+
+        let rejected = webhook_message("Solo H", false, "Man", Some("Invalid source"));
+        assert_eq!(rejected, "Solo H Man ❌ Invalid source");
+        assert_eq!(
+            webhook_message("Solo H", true, "Man", None),
+            "Solo H Man ✅"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unconfirmed_member_credited_once() {
+        use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+
+        let path = std::env::temp_dir().join(format!("identity-test-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let options = SqliteConnectOptions::new()
+            .filename(&path)
+            .create_if_missing(true);
+        let pool = SqlitePoolOptions::new()
+            .connect_with(options)
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+
+        sqlx::query("INSERT INTO teams (id, name) VALUES (99, 'hammered')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO team_members (id, team_id, username, pending_account_hash) VALUES (99, 99, 'solo h', 'abc123')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let loots = vec![("Bones".to_string(), 1), ("Coins".to_string(), 15)];
+        let channel = DinkDrop::new("Solo H".to_string(), "Man".to_string(), loots.clone());
+        let mut webhook = DinkDrop::new("Solo H".to_string(), "Man".to_string(), loots);
+        webhook.account_hash = Some("abc123".to_string());
+        webhook.ingest = IngestPath::Webhook;
+
+        let mut credited = 0;
+        for drop in [&webhook, &channel] {
+            let fingerprint = drop.fingerprint(&drop_identity(&pool, drop).await.unwrap());
+            let inserted = database::insert_drop_fingerprint(
+                &pool,
+                &fingerprint,
+                &drop.user,
+                &drop.source,
+                0,
+                60,
+            )
+            .await
+            .unwrap();
+            credited += inserted.is_some() as i32;
+        }
+        assert_eq!(credited, 1);
+
+        // Once confirmed, both copies are identified by the hash
+        sqlx::query("UPDATE team_members SET account_hash = 'abc123' WHERE id = 99")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(drop_identity(&pool, &webhook).await.unwrap(), "abc123");
+        assert_eq!(drop_identity(&pool, &channel).await.unwrap(), "abc123");
+
+        pool.close().await;
+        let _ = std::fs::remove_file(&path);
+    }
 }
 
 pub struct DinkDrop {
     pub user: String,
     pub source: String,
     pub loots: Vec<(String, u32)>,
+    /// Item IDs and GE values, only available when built from Dink's `extra` data
+    pub items: Vec<LootItem>,
+    /// Dink's per-account hash, only available on webhook payloads
+    pub account_hash: Option<String>,
+    /// When the drop happened, or when it was received if Dink didn't say
+    pub timestamp: DateTime<Utc>,
+    pub ingest: IngestPath,
+    /// The Discord message a channel drop was read from
+    pub message_id: Option<u64>,
+    /// The ingest queue row a webhook drop was read from
+    pub queue_id: Option<i64>,
+}
+
+/// How a drop reached the bot
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IngestPath {
+    /// Posted to the webhook server by the Dink plugin
+    Webhook,
+    /// Read from a Dink message in the updates channel
+    Channel,
+}
+
+impl IngestPath {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IngestPath::Webhook => "webhook",
+            IngestPath::Channel => "channel",
+        }
+    }
 }
 
 impl DinkDrop {
@@ -40,8 +252,80 @@ impl DinkDrop {
             user,
             source,
             loots,
+            items: Vec::new(),
+            account_hash: None,
+            timestamp: Utc::now(),
+            ingest: IngestPath::Channel,
+            message_id: None,
+            queue_id: None,
+        }
+    }
+
+    /// Builds a drop from the structured data of a LOOT notification
+    pub fn from_loot_extra(user: &str, extra: &LootExtra) -> Self {
+        Self::from_items(user, &extra.source, &extra.items)
+    }
+
+    /// Builds a drop from the casket loot of a CLUE notification, named like
+    /// the casket in a loot message (e.g. "Reward casket (hard)")
+    pub fn from_clue_extra(user: &str, extra: &ClueExtra) -> Self {
+        let source = format!("Reward casket ({})", extra.clue_type.to_lowercase());
+        Self::from_items(user, &source, &extra.items)
+    }
+
+    fn from_items(user: &str, source: &str, items: &[LootItem]) -> Self {
+        let loots = items
+            .iter()
+            .map(|item| (item.name.clone(), item.quantity))
+            .collect();
+
+        Self {
+            user: user.to_string(),
+            source: source.to_string(),
+            loots,
+            items: items.to_vec(),
+            account_hash: None,
+            timestamp: Utc::now(),
+            ingest: IngestPath::Webhook,
+            message_id: None,
+            queue_id: None,
         }
     }
+
+    /// Hash of the account identity, source and items, used to spot the same
+    /// drop being reported more than once. The hash is stable across builds
+    /// since fingerprints are stored.
+    pub fn fingerprint(&self, identity: &str) -> String {
+        let mut loots: Vec<(String, u32)> = self
+            .loots
+            .iter()
+            .map(|(name, quantity)| (name.to_lowercase(), *quantity))
+            .collect();
+        loots.sort();
+
+        let canonical = serde_json::json!([identity, self.source.to_lowercase(), loots]);
+        Sha256::digest(canonical.to_string().as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// The items as "N x Item", for logs admins read back
+    pub fn items_summary(&self) -> String {
+        self.loots
+            .iter()
+            .map(|(name, quantity)| format!("{} x {}", quantity, name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Total GE value of the drop, if item values are known
+    pub fn total_value(&self) -> i64 {
+        self.items
+            .iter()
+            .map(|item| item.price_each * item.quantity as i64)
+            .sum()
+    }
 }
 /// Handles a message sent in the dink channel.
 /// If the message contains embeds, attempts to parse each embed description
@@ -56,231 +340,553 @@ pub async fn handle_message(
 
     for embed in &new_message.embeds {
         // println!("loot embed: {:?}", embed);
-        let description = match &embed.description {
-            Some(desc) => desc,
-            None => continue,
-        };
+        let description = embed.description.as_deref().unwrap_or_default();
+        if description.is_empty() && embed.fields.is_empty() {
+            continue;
+        }
 
         // println!("dink embed description is: '{}'", description);
 
-        let drop = parse_loot_text(description)?;
+        let author = embed.author.as_ref().map(|author| author.name.as_str());
+        let fields: Vec<(&str, &str)> = embed
+            .fields
+            .iter()
+            .map(|field| (field.name.as_str(), field.value.as_str()))
+            .collect();
+        let drops = parser::parse_loot_embed(author, description, &fields)?;
+
+        for mut drop in drops {
+            // Dink's own embed timestamp matches the one on its webhook payload
+            let timestamp = embed.timestamp.unwrap_or(new_message.timestamp);
+            if let Some(timestamp) = DateTime::from_timestamp(timestamp.unix_timestamp(), 0) {
+                drop.timestamp = timestamp;
+            }
+            drop.message_id = Some(new_message.id.get());
 
-        println!(
-            "Processing drop: User: {}, Source: {}, Items: {:?}",
-            drop.user, drop.source, drop.loots
-        );
+            println!(
+                "Processing drop: User: {}, Source: {}, Items: {:?}",
+                drop.user, drop.source, drop.loots
+            );
 
-        process_drop(ctx, data, drop).await?;
+            process_drop(&ctx.http, data, drop).await?;
+        }
     }
 
     Ok(())
 }
 
+/// Handles a message deleted from the dink channel by reverting the drops
+/// that were credited from it
+pub async fn handle_message_delete(
+    http: &serenity::Http,
+    data: &Data,
+    message_id: serenity::MessageId,
+) -> Result<(), Error> {
+    let drop_ids =
+        database::get_ledger_drops_by_message(&data.database, message_id.get() as i64).await?;
+
+    let actor = coc::admin::Actor::discord("dink message deleted");
+    for drop_id in drop_ids {
+        let (outcome, team_name) = coc::admin::revert_drop(data, &actor, drop_id).await?;
+
+        match outcome {
+            Ok(message) => println!("{}", message),
+            Err(refusal) => println!("Could not revert drop #{}: {}", drop_id, refusal),
+        }
+
+        if let Some(team_name) = team_name {
+            if let Err(e) = update_team_embeds(http, data, &team_name).await {
+                eprintln!("Error updating team embeds for '{}': {}", team_name, e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Why a drop was not credited to a team
+#[derive(Debug, Clone, PartialEq)]
+pub enum RejectReason {
+    NoTeam,
+    CombatLevel(u32),
+    SlayerLevel(u32),
+    /// The source needs a building level the team hasn't reached
+    Building {
+        source: String,
+        building: String,
+        level: u32,
+    },
+    InvalidSource,
+    Duplicate,
+    AccountMismatch,
+    SeasonalWorld,
+    World(i32),
+    Region(i32),
+    Clan(String),
+    /// The event starts at the given unix timestamp
+    EventNotStarted(i64),
+    /// The event ended at the given unix timestamp
+    EventEnded(i64),
+    EventPaused,
+}
+
+impl RejectReason {
+    /// Every reason code, for filtering the rejection log
+    pub const CODES: &'static [&'static str] = &[
+        "no_team",
+        "combat_level",
+        "slayer_level",
+        "building",
+        "invalid_source",
+        "duplicate",
+        "account_mismatch",
+        "seasonal_world",
+        "world",
+        "region",
+        "clan",
+        "event_not_started",
+        "event_ended",
+        "event_paused",
+    ];
+
+    /// Short machine-readable code for the reason
+    pub fn code(&self) -> &'static str {
+        match self {
+            RejectReason::NoTeam => "no_team",
+            RejectReason::CombatLevel(_) => "combat_level",
+            RejectReason::SlayerLevel(_) => "slayer_level",
+            RejectReason::Building { .. } => "building",
+            RejectReason::InvalidSource => "invalid_source",
+            RejectReason::Duplicate => "duplicate",
+            RejectReason::AccountMismatch => "account_mismatch",
+            RejectReason::SeasonalWorld => "seasonal_world",
+            RejectReason::World(_) => "world",
+            RejectReason::Region(_) => "region",
+            RejectReason::Clan(_) => "clan",
+            RejectReason::EventNotStarted(_) => "event_not_started",
+            RejectReason::EventEnded(_) => "event_ended",
+            RejectReason::EventPaused => "event_paused",
+        }
+    }
+}
+
+impl std::fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RejectReason::NoTeam => write!(f, "Not in any team"),
+            RejectReason::CombatLevel(_) => write!(f, "Team lacks access to this combat level"),
+            RejectReason::SlayerLevel(_) => write!(f, "Team lacks access to this slayer level"),
+            RejectReason::Building { source, .. } => write!(f, "Team lacks access to {}", source),
+            RejectReason::InvalidSource => write!(f, "Invalid source"),
+            RejectReason::Duplicate => write!(f, "Duplicate drop"),
+            RejectReason::AccountMismatch => {
+                write!(f, "Account doesn't match the registered player")
+            }
+            RejectReason::SeasonalWorld => write!(f, "Seasonal worlds don't count"),
+            RejectReason::World(world) => write!(f, "World {} doesn't count", world),
+            RejectReason::Region(region) => write!(f, "Region {} doesn't count", region),
+            RejectReason::Clan(clan) if clan.is_empty() => write!(f, "Not in an event clan"),
+            RejectReason::Clan(clan) => write!(f, "Clan {} isn't part of the event", clan),
+            RejectReason::EventNotStarted(starts_at) => write!(
+                f,
+                "The event hasn't started, it starts {}",
+                schedule::format_time(*starts_at)
+            ),
+            RejectReason::EventEnded(ends_at) => {
+                write!(f, "The event ended {}", schedule::format_time(*ends_at))
+            }
+            RejectReason::EventPaused => write!(f, "The event is paused"),
+        }
+    }
+}
+
+/// Result of running a drop through `process_drop`
+#[derive(Debug, Clone, PartialEq)]
+pub enum DropOutcome {
+    Credited,
+    Rejected(RejectReason),
+}
+
+/// Logs a rejected drop, sends its failure webhook and returns the outcome.
+/// A failed webhook is only logged, so a retry doesn't log the rejection again.
+pub async fn reject(
+    data: &Data,
+    drop: &DinkDrop,
+    reason: RejectReason,
+) -> Result<DropOutcome, Error> {
+    log_rejection(data, drop, &reason).await;
+    if let Err(e) = send_webhook(&drop.user, false, &drop.source, Some(&reason.to_string())).await {
+        eprintln!("Error sending rejection webhook for '{}': {}", drop.user, e);
+    }
+    Ok(DropOutcome::Rejected(reason))
+}
+
+/// Records a rejected drop in the rejection log. Failures are only logged,
+/// the drop is rejected either way.
+async fn log_rejection(data: &Data, drop: &DinkDrop, reason: &RejectReason) {
+    let pool = &data.database;
+
+    // Resolve the team like crediting would, following the account hash,
+    // without applying any member updates
+    let team_name = match find_team(pool, drop).await {
+        Ok(team) => team.ok().map(|team| team.team_name),
+        Err(e) => {
+            eprintln!("Error looking up team of '{}': {}", drop.user, e);
+            None
+        }
+    };
+
+    let result = database::insert_drop_rejection(
+        pool,
+        &database::Rejection {
+            player: &drop.user,
+            team_name: team_name.as_deref(),
+            source: &drop.source,
+            reason_code: reason.code(),
+            reason: &reason.to_string(),
+            items: &drop.items_summary(),
+            ingest_path: drop.ingest.as_str(),
+            queue_id: drop.queue_id,
+            message_id: drop.message_id.map(|id| id as i64),
+            dropped_at: drop.timestamp.timestamp(),
+        },
+    )
+    .await;
+
+    if let Err(e) = result {
+        eprintln!("Error logging rejected drop from '{}': {}", drop.user, e);
+    }
+}
+
+/// Identifies the account a drop belongs to, the same way on both ingest paths.
+///
+/// Drops read from the channel carry no account hash, so a hash is only used
+/// once an admin confirmed it on a member, and both copies can look it up.
+/// Otherwise the lowercase player name is used.
+pub async fn drop_identity(pool: &sqlx::SqlitePool, drop: &DinkDrop) -> Result<String, Error> {
+    if let Some(account_hash) = &drop.account_hash {
+        if database::get_member_by_account_hash(pool, account_hash)
+            .await?
+            .is_some()
+        {
+            return Ok(account_hash.clone());
+        }
+    }
+
+    let username = drop.user.to_lowercase();
+    match database::get_member_by_username(pool, &username).await? {
+        Some((_, _, _, Some(account_hash))) => Ok(account_hash),
+        _ => Ok(username),
+    }
+}
+
 /// Processes a dink drop
 ///
-/// Check that the user is in the database - if not, return early.
-/// For each loot, query the hash table to determine if it is of note.
-/// For each noteworthy loot, update the quantity in resources for the player's
-/// team.
+/// Reject drops made outside the event window or while it's paused, and skip
+/// the drop if the same drop was already seen within the duplicate window,
+/// then credit it. If crediting fails the fingerprint is released so
+/// a retry isn't mistaken for a duplicate.
 pub async fn process_drop(
-    ctx: &serenity::Context,
+    http: &serenity::Http,
     data: &Data,
     drop: DinkDrop,
-) -> Result<(), Error> {
+) -> Result<DropOutcome, Error> {
     let pool = &data.database;
 
-    let username = drop.user.to_lowercase();
+    let dropped_at = drop.timestamp.timestamp();
+    let schedule = schedule::EventSchedule::load(pool).await?;
+    if let Err(reason) = schedule.check(dropped_at) {
+        println!(
+            "Skipping drop from '{}' ({}) outside the event: {}",
+            drop.user, drop.source, reason
+        );
+        let event = DropEvent::new(&drop);
+        let outcome = reject(data, &drop, reason).await?;
+        data.metrics.drop_outcome(&outcome);
+        data.drop_events.publish(event.finish(&outcome));
+        return Ok(outcome);
+    }
 
-    let team = match get_user_team(pool, &username).await {
-        Ok(Some(team)) => team,
-        Ok(None) => {
-            println!("User '{}' is not in any team, ignoring drop", drop.user);
-            send_webhook(&drop.user, false, &drop.source, Some("Not in any team")).await?;
-            return Ok(());
-        }
-        Err(e) => {
-            println!("Database error when checking user team: {}", e);
-            send_webhook(&drop.user, false, &drop.source, Some("Database error")).await?;
-            return Err(e.into());
+    let fingerprint = drop.fingerprint(&drop_identity(pool, &drop).await?);
+    let window = data.duplicate_window_secs;
+
+    let fingerprint_id = match database::insert_drop_fingerprint(
+        pool,
+        &fingerprint,
+        &drop.user,
+        &drop.source,
+        dropped_at,
+        window,
+    )
+    .await?
+    {
+        Some(id) => id,
+        None => {
+            let original =
+                database::get_drop_fingerprint(pool, &fingerprint, dropped_at, window).await?;
+            let reason = match original {
+                Some((id, original_at)) => format!(
+                    "Same drop as #{} reported {}s apart",
+                    id,
+                    (dropped_at - original_at).abs()
+                ),
+                None => "Same drop already reported".to_string(),
+            };
+
+            println!(
+                "Skipping duplicate drop from '{}' ({}): {}",
+                drop.user, drop.source, reason
+            );
+            database::insert_duplicate_drop(
+                pool,
+                &fingerprint,
+                original.map(|(id, _)| id),
+                &drop.user,
+                &drop.source,
+                &drop.items_summary(),
+                &reason,
+                dropped_at,
+            )
+            .await?;
+
+            // No feedback webhook here, the original drop already sent one
+            log_rejection(data, &drop, &RejectReason::Duplicate).await;
+            let outcome = DropOutcome::Rejected(RejectReason::Duplicate);
+            data.metrics.drop_outcome(&outcome);
+            data.drop_events
+                .publish(DropEvent::new(&drop).finish(&outcome));
+            return Ok(outcome);
         }
     };
 
-    // Check if the source has a valid combat level
-    match data.bestiary.get_combat_level(&drop.source) {
-        Some(level) => {
-            let source_combat_level = level as i32;
+    let mut event = DropEvent::new(&drop);
+    let result = credit_drop(http, data, &drop, fingerprint_id, &mut event).await;
 
-            // Check if team has access to monsters of this combat level
-            if !get_team_armory_level(pool, source_combat_level, team.0)
-                .await?
-                .unwrap_or(false)
-            {
-                println!(
-                    "Team '{}' doesn't have access to combat level {} monsters",
-                    team.1, source_combat_level
-                );
-                send_webhook(
-                    &drop.user,
-                    false,
-                    &drop.source,
-                    Some("Team lacks access to this combat level"),
-                )
-                .await?;
-                return Ok(());
-            }
+    match &result {
+        Ok(outcome) => {
+            data.metrics.drop_outcome(outcome);
+            data.drop_events.publish(event.finish(outcome));
+        }
+        Err(_) => database::delete_drop_fingerprint(pool, fingerprint_id).await?,
+    }
+
+    result
+}
 
-            match data.bestiary.get_slayer_level(&drop.source) {
-                Some(level) => {
-                    println!("Slayer level for source '{}': {}", drop.source, level);
-
-                    // Check if team has necessary slayer level
-                    if !get_team_slayer_level(pool, level as i32, team.0)
-                        .await?
-                        .unwrap_or(false)
-                    {
-                        println!(
-                            "Team '{}' doesn't have access to slayer level {} monsters",
-                            team.1, level
-                        );
-                        send_webhook(
-                            &drop.user,
-                            false,
-                            &drop.source,
-                            Some("Team lacks access to this slayer level"),
-                        )
-                        .await?;
-                        return Ok(());
-                    }
+/// The team a drop belongs to, and the member updates crediting it implies
+pub struct TeamMatch {
+    pub team_id: i32,
+    pub team_name: String,
+    /// Member who changed name since registering, and their new name
+    rename: Option<(i32, String)>,
+    /// Unbound member to store the drop's account hash on as pending
+    pending_hash: Option<(i32, String)>,
+}
+
+/// Finds the team a drop should be credited to, without writing anything
+///
+/// Drops with an account hash are matched on the member's confirmed hash
+/// first, which follows the player through name changes. Otherwise the player
+/// name is used, and a hash that differs from the member's confirmed one is
+/// refused. Unbound members keep the hash as pending until an admin confirms it.
+pub async fn find_team(
+    pool: &sqlx::SqlitePool,
+    drop: &DinkDrop,
+) -> Result<Result<TeamMatch, RejectReason>, Error> {
+    let username = drop.user.to_lowercase();
+
+    if let Some(account_hash) = &drop.account_hash {
+        if let Some((member_id, team_id, team_name, registered_name)) =
+            database::get_member_by_account_hash(pool, account_hash).await?
+        {
+            let mut rename = None;
+            if registered_name != username {
+                if database::get_member_by_username(pool, &username)
+                    .await?
+                    .is_none()
+                {
+                    println!(
+                        "Player '{}' is now known as '{}'",
+                        registered_name, username
+                    );
+                    rename = Some((member_id, username));
+                } else {
+                    println!(
+                        "Not renaming '{}' to '{}', the name is already registered",
+                        registered_name, username
+                    );
                 }
-                None => {}
             }
+
+            return Ok(Ok(TeamMatch {
+                team_id,
+                team_name,
+                rename,
+                pending_hash: None,
+            }));
         }
-        None => {
-            let garrisons_level =
-                database::get_team_building_level(pool, team.0, "garrisons").await?;
-
-            if drop.source.to_lowercase() == "lunar chest" {
-                if garrisons_level < 2 {
-                    println!("Team '{}' doesn't have access to Lunar Chests", team.1);
-                    send_webhook(
-                        &drop.user,
-                        false,
-                        &drop.source,
-                        Some("Team lacks access to Lunar Chests"),
-                    )
-                    .await?;
-                    return Ok(());
-                }
-            } else if drop.source.to_lowercase() == "fortis colosseum" {
-                if garrisons_level < 3 {
-                    println!("Team '{}' doesn't have access to Fortis Colosseum", team.1);
-                    send_webhook(
-                        &drop.user,
-                        false,
-                        &drop.source,
-                        Some("Team lacks access to Fortis Colosseum"),
-                    )
-                    .await?;
-                    return Ok(());
-                }
-            } else if drop.source.to_lowercase() == "tombs of amascut"
-                || drop.source.to_lowercase() == "tombs of amascut: expert mode"
+    }
+
+    let (member_id, team_id, team_name, bound_hash) =
+        match database::get_member_by_username(pool, &username).await? {
+            Some(member) => member,
+            None => return Ok(Err(RejectReason::NoTeam)),
+        };
+
+    match (&drop.account_hash, bound_hash) {
+        (Some(account_hash), Some(_)) => {
+            // The name is bound to a different account
+            println!(
+                "Account hash '{}' doesn't match the account bound to '{}'",
+                account_hash, username
+            );
+            Ok(Err(RejectReason::AccountMismatch))
+        }
+        (account_hash, _) => Ok(Ok(TeamMatch {
+            team_id,
+            team_name,
+            rename: None,
+            pending_hash: account_hash.clone().map(|hash| (member_id, hash)),
+        })),
+    }
+}
+
+/// Resolves the team a drop should be credited to, applying any rename or
+/// pending account hash found along the way
+async fn resolve_team(
+    pool: &sqlx::SqlitePool,
+    drop: &DinkDrop,
+) -> Result<Result<(i32, String), RejectReason>, Error> {
+    let team = match find_team(pool, drop).await? {
+        Ok(team) => team,
+        Err(reason) => return Ok(Err(reason)),
+    };
+
+    if let Some((member_id, username)) = &team.rename {
+        database::update_team_member_username(pool, *member_id, username).await?;
+    }
+    if let Some((member_id, account_hash)) = &team.pending_hash {
+        database::set_pending_account_hash(pool, *member_id, account_hash).await?;
+    }
+
+    Ok(Ok((team.team_id, team.team_name)))
+}
+
+/// Credits a drop to the player's team
+///
+/// Check that the user is in the database - if not, return early.
+/// For each loot, query the hash table to determine if it is of note.
+/// For each noteworthy loot, update the quantity in resources for the player's
+/// team. The team and credited quantities are recorded on `event`.
+async fn credit_drop(
+    http: &serenity::Http,
+    data: &Data,
+    drop: &DinkDrop,
+    drop_id: i64,
+    event: &mut DropEvent,
+) -> Result<DropOutcome, Error> {
+    let pool = &data.database;
+
+    let team = match resolve_team(pool, drop).await {
+        Ok(Ok(team)) => team,
+        Ok(Err(reason)) => {
+            println!("Ignoring drop from '{}': {}", drop.user, reason);
+            return reject(data, drop, reason).await;
+        }
+        Err(e) => {
+            println!("Database error when checking user team: {}", e);
+            if let Err(webhook_error) =
+                send_webhook(&drop.user, false, &drop.source, Some("Database error")).await
             {
-                if garrisons_level < 4 {
-                    println!("Team '{}' doesn't have access to Tombs of Amascut", team.1);
-                    send_webhook(
-                        &drop.user,
-                        false,
-                        &drop.source,
-                        Some("Team lacks access to Tombs of Amascut"),
-                    )
-                    .await?;
-                    return Ok(());
-                }
-            } else if drop.source.to_lowercase() == "chambers of xeric" {
-                if garrisons_level < 5 {
-                    println!("Team '{}' doesn't have access to Chambers of Xeric", team.1);
-                    send_webhook(
-                        &drop.user,
-                        false,
-                        &drop.source,
-                        Some("Team lacks access to Chambers of Xeric"),
-                    )
-                    .await?;
-                    return Ok(());
-                }
-            } else if drop.source.to_lowercase() == "theatre of blood" {
-                if garrisons_level < 6 {
-                    println!("Team '{}' doesn't have access to Theatre of Blood", team.1);
-                    send_webhook(
-                        &drop.user,
-                        false,
-                        &drop.source,
-                        Some("Team lacks access to Theatre of Blood"),
-                    )
-                    .await?;
-                    return Ok(());
-                }
-            } else {
-                println!("No combat level found for source '{}'", drop.source);
-                send_webhook(&drop.user, false, &drop.source, Some("Invalid source")).await?;
-                return Ok(());
+                eprintln!(
+                    "Error sending drop webhook for '{}': {}",
+                    drop.user, webhook_error
+                );
             }
+            return Err(e);
         }
     };
+    event.team = Some(team.1.clone());
 
-    // Process each item in the drop
-    for (item_name, quantity) in drop.loots {
-        let quantity = quantity as i64;
-        let item_name = item_name.to_lowercase();
+    // Check the team can credit drops from this source
+    let check = evaluate::check_source(data, team.0, &drop.source).await?;
+    if let Err(reason) = check.result {
+        println!(
+            "Team '{}' can't credit drops from '{}': {}",
+            team.1, drop.source, reason
+        );
+        return reject(data, drop, reason).await;
+    }
 
-        let result =
-            coc::patterns::matches_pattern(&item_name, &data.res_patterns.resource_pattern);
+    // Work out each item's credit first, then write them all at once
+    let mut items = Vec::new();
+    for (index, (item_name, raw_quantity)) in drop.loots.iter().enumerate() {
+        let item =
+            evaluate::evaluate_item(data, team.0, check.access, item_name, *raw_quantity).await?;
 
-        if !result {
-            continue;
+        if let (Some(category), Some(calculation)) = (item.category, item.calculation) {
+            items.push((index, item.name, category, calculation));
         }
+    }
 
-        let category =
-            coc::patterns::get_resource_category(&item_name, &data.res_patterns.resource_pattern);
-
-        let quantity =
-            coc::database::calculate_resource_total(pool, quantity as i32, team.0, &category)
-                .await?;
+    let entries: Vec<database::LedgerEntry> = items
+        .iter()
+        .map(
+            |(index, item_name, category, calculation)| database::LedgerEntry {
+                drop_id: Some(drop_id),
+                player: &drop.user,
+                team_id: team.0,
+                source: &drop.source,
+                item_name,
+                category,
+                raw_quantity: drop.loots[*index].1 as i64,
+                calculation,
+                ingest_path: drop.ingest.as_str(),
+                message_id: drop.message_id.map(|id| id as i64),
+                dropped_at: drop.timestamp.timestamp(),
+            },
+        )
+        .collect();
+    database::credit_drop_items(pool, &entries).await?;
 
-        let existing_resource = get_resource_quantity_by_name(pool, team.0, &item_name).await?;
+    let mut credited: HashMap<&str, u64> = HashMap::new();
+    for (index, _, category, calculation) in &items {
+        event.items[*index].credited = calculation.total as i64;
+        *credited.entry(category).or_insert(0) += calculation.total.max(0) as u64;
+    }
 
-        match existing_resource {
-            Some(resource) => {
-                let new_quantity = resource + quantity as i64;
-                update_resource_quantity(pool, team.0, &item_name, new_quantity).await?;
-            }
-            None => {
-                insert_new_resource(pool, team.0, &item_name, &category, quantity as i64).await?;
-            }
-        }
+    for (category, quantity) in &credited {
+        data.metrics.items_credited(category, *quantity);
     }
 
+    // The drop is credited from here on, so later failures are only logged
+    // to avoid a retry crediting it twice
+
     // Throttle embed updates - only update after certain time interval
     let team_name = &team.1;
     let update_needed = should_update_team_embeds(data, team_name).await;
 
     if update_needed {
         println!("Updating team embeds for '{}'", team_name);
-        update_team_embeds(ctx, data, team_name).await?;
+        if let Err(e) = update_team_embeds(http, data, team_name).await {
+            eprintln!("Error updating team embeds for '{}': {}", team_name, e);
+        }
     } else {
         println!("Skipping team embed update (throttled) for '{}'", team_name);
     }
 
-    send_webhook(
+    if let Err(e) = send_webhook(
         &drop.user,
         true,
         &drop.source,
-        Some("Drop processed successfully"),
+        Some(&credited_message(drop_id)),
     )
-    .await?;
+    .await
+    {
+        eprintln!("Error sending drop webhook: {}", e);
+    }
 
-    Ok(())
+    Ok(DropOutcome::Credited)
 }
 
 // Add to Data struct a field:
@@ -305,6 +911,25 @@ async fn should_update_team_embeds(data: &Data, team_name: &str) -> bool {
     true
 }
 
+/// Formats the feedback message for a processed drop
+fn webhook_message(
+    player_name: &str,
+    status: bool,
+    source: &str,
+    optional_message: Option<&str>,
+) -> String {
+    let status_emoji = if status { "✅" } else { "❌" };
+    match optional_message {
+        Some(message) => format!("{} {} {} {}", player_name, source, status_emoji, message),
+        None => format!("{} {} {}", player_name, source, status_emoji),
+    }
+}
+
+/// Feedback for a credited drop, carrying the id admins revert it by
+fn credited_message(drop_id: i64) -> String {
+    format!("Drop #{} processed successfully", drop_id)
+}
+
 /// Sends a Discord webhook message.
 ///
 /// # Arguments
@@ -329,18 +954,7 @@ pub async fn send_webhook(
         }
     };
 
-    let status_emoji = if status { "✅" } else { "❌" };
-    let message = if status {
-        format!("{} {} {}", player_name, source, status_emoji)
-    } else {
-        format!(
-            "{} {} {} {}",
-            player_name,
-            source,
-            status_emoji,
-            optional_message.unwrap_or("")
-        )
-    };
+    let message = webhook_message(player_name, status, source, optional_message);
 
     let client = Client::new();
     let payload = serde_json::json!({
@@ -393,47 +1007,3 @@ pub async fn send_webhook(
 
     Ok(())
 }
-
-/// Parse loot text into structured format
-///
-/// Input format: "User has looted: \n\n# x [Item](url) (value)\n# x [Item](url) (value)\nFrom: [Source](url)"
-/// Output: (username, Vec<(item_name, quantity)>, source)
-pub fn parse_loot_text(text: &str) -> Result<DinkDrop, Error> {
-    // Extract username (assumes format "Username has looted:")
-    let username = match text.split(" has looted:").next() {
-        Some(name) => name.trim().to_string(),
-        None => return Err("Could not find username in loot text".into()),
-    };
-
-    // Return err if username longer than 15 characters
-    if username.len() > 15 {
-        return Err("Username is too long (exceeds 15 characters)".into());
-    }
-
-    // Split the text into lines and process each line%USERNAME% has looted:
-    let mut loots = Vec::new();
-    let mut source = String::new();
-
-    for line in text.lines() {
-        // Parse loot lines (format: "# x [Item](url) (value)")
-        if let Some(captures) = line.trim().split_once(" x [") {
-            let quantity = captures.0.trim().parse::<u32>().unwrap_or(0);
-            if let Some(item_name) = captures.1.split("](").next() {
-                loots.push((item_name.to_string(), quantity));
-            }
-        }
-
-        // Parse source line (format: "From: [Source](url)")
-        if line.trim().starts_with("From: [") {
-            if let Some(src) = line.trim()[7..].split("](").next() {
-                source = src.to_string();
-            }
-        }
-    }
-
-    if loots.is_empty() {
-        return Err("No loots found in loot text".into());
-    }
-
-    Ok(DinkDrop::new(username, source, loots))
-}
@@ -0,0 +1,608 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude as serenity;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::coc::database;
+use crate::dink::{DinkDrop, DropOutcome, IngestPath};
+use crate::webhook::WebhookPayload;
+use crate::{dink, Data, Error};
+
+/// A single item as reported in Dink's `extra.items`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LootItem {
+    pub id: u32,
+    pub name: String,
+    pub quantity: u32,
+    #[serde(default)]
+    pub price_each: i64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LootExtra {
+    #[serde(default)]
+    pub items: Vec<LootItem>,
+    #[serde(default)]
+    pub source: String,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub kill_count: Option<u32>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PetExtra {
+    #[serde(default)]
+    pub pet_name: Option<String>,
+    #[serde(default)]
+    pub milestone: Option<String>,
+    #[serde(default)]
+    pub duplicate: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionExtra {
+    pub item_name: String,
+    #[serde(default)]
+    pub item_id: Option<u32>,
+    #[serde(default)]
+    pub price: Option<i64>,
+    #[serde(default)]
+    pub completed_entries: Option<u32>,
+    #[serde(default)]
+    pub total_entries: Option<u32>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CombatLevel {
+    pub value: u32,
+    pub increased: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LevelExtra {
+    pub levelled_skills: HashMap<String, u32>,
+    #[serde(default)]
+    pub all_skills: HashMap<String, u32>,
+    #[serde(default)]
+    pub combat_level: Option<CombatLevel>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KillCountExtra {
+    pub boss: String,
+    pub count: u32,
+    #[serde(default)]
+    pub game_message: Option<String>,
+    #[serde(default)]
+    pub time: Option<String>,
+    #[serde(default)]
+    pub is_personal_best: Option<bool>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClueExtra {
+    pub clue_type: String,
+    pub number_completed: u32,
+    #[serde(default)]
+    pub items: Vec<LootItem>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlayerExtra {
+    pub slayer_task: String,
+    #[serde(default)]
+    pub slayer_completed: Option<String>,
+    #[serde(default)]
+    pub slayer_points: Option<String>,
+    #[serde(default)]
+    pub kill_count: Option<u32>,
+    #[serde(default)]
+    pub monster: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CombatAchievementExtra {
+    pub tier: String,
+    pub task: String,
+    #[serde(default)]
+    pub task_points: Option<u32>,
+    #[serde(default)]
+    pub total_points: Option<u32>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeathExtra {
+    #[serde(default)]
+    pub value_lost: i64,
+    #[serde(default)]
+    pub is_pvp: bool,
+    #[serde(default)]
+    pub killer_name: Option<String>,
+    #[serde(default)]
+    pub killer_npc_id: Option<u32>,
+}
+
+/// A Dink notification with its `extra` object deserialized according to `type`
+#[derive(Debug, Clone)]
+pub enum DinkNotification {
+    Loot(LootExtra),
+    Pet(PetExtra),
+    Collection(CollectionExtra),
+    Level(LevelExtra),
+    KillCount(KillCountExtra),
+    Clue(ClueExtra),
+    Slayer(SlayerExtra),
+    CombatAchievement(CombatAchievementExtra),
+    Death(DeathExtra),
+    /// Any notification type we don't have a typed representation for yet
+    Other(String),
+}
+
+impl DinkNotification {
+    /// Builds the typed notification from a webhook payload
+    pub fn from_payload(payload: &WebhookPayload) -> Result<Self, serde_json::Error> {
+        let notification = match payload.r#type.as_str() {
+            "LOOT" => DinkNotification::Loot(extra(payload)?),
+            "PET" => DinkNotification::Pet(extra(payload)?),
+            "COLLECTION" => DinkNotification::Collection(extra(payload)?),
+            "LEVEL" => DinkNotification::Level(extra(payload)?),
+            "KILL_COUNT" => DinkNotification::KillCount(extra(payload)?),
+            "CLUE" => DinkNotification::Clue(extra(payload)?),
+            "SLAYER" => DinkNotification::Slayer(extra(payload)?),
+            "COMBAT_ACHIEVEMENT" => DinkNotification::CombatAchievement(extra(payload)?),
+            "DEATH" => DinkNotification::Death(extra(payload)?),
+            other => DinkNotification::Other(other.to_string()),
+        };
+
+        Ok(notification)
+    }
+}
+
+fn extra<T: DeserializeOwned>(payload: &WebhookPayload) -> Result<T, serde_json::Error> {
+    serde_json::from_value(payload.extra.clone())
+}
+
+/// Routes a notification to the handler for its type.
+/// Returns the outcome of every drop the notification produced.
+pub async fn dispatch(
+    http: &serenity::Http,
+    data: &Data,
+    payload: &WebhookPayload,
+    notification: DinkNotification,
+) -> Result<Vec<DropOutcome>, Error> {
+    match notification {
+        DinkNotification::Loot(extra) => on_loot(http, data, payload, extra).await,
+        DinkNotification::Pet(extra) => on_pet(http, data, payload, extra).await,
+        DinkNotification::Collection(extra) => on_collection(http, data, payload, extra).await,
+        DinkNotification::Level(extra) => on_level(http, data, payload, extra).await,
+        DinkNotification::KillCount(extra) => on_kill_count(http, data, payload, extra).await,
+        DinkNotification::Clue(extra) => on_clue(http, data, payload, extra).await,
+        DinkNotification::Slayer(extra) => on_slayer(http, data, payload, extra).await,
+        DinkNotification::CombatAchievement(extra) => {
+            on_combat_achievement(http, data, payload, extra).await
+        }
+        DinkNotification::Death(extra) => on_death(http, data, payload, extra).await,
+        DinkNotification::Other(kind) => {
+            println!(
+                "Unhandled {} notification from {}",
+                kind, payload.playerName
+            );
+            post_raw_event(http, data, payload).await?;
+            Ok(Vec::new())
+        }
+    }
+}
+
+/// Credits loot from the structured `extra` data, falling back to parsing
+/// the embed descriptions when Dink didn't include the item list. Drops from
+/// worlds, regions or clans outside the event policy are rejected without crediting.
+async fn on_loot(
+    http: &serenity::Http,
+    data: &Data,
+    payload: &WebhookPayload,
+    extra: LootExtra,
+) -> Result<Vec<DropOutcome>, Error> {
+    let mut drops = Vec::new();
+
+    if !extra.items.is_empty() && !extra.source.is_empty() {
+        let drop = DinkDrop::from_loot_extra(&payload.playerName, &extra);
+
+        println!(
+            "Processing drop: User: {}, Source: {}, Items: {:?}, Value: {}",
+            drop.user,
+            drop.source,
+            drop.loots,
+            drop.total_value()
+        );
+
+        drops.push(drop);
+    } else {
+        for embed in &payload.embeds {
+            let author = embed.author.as_ref().map(|author| author.name.as_str());
+            let fields: Vec<(&str, &str)> = embed
+                .fields
+                .iter()
+                .flatten()
+                .map(|field| (field.name.as_str(), field.value.as_str()))
+                .collect();
+
+            match dink::parser::parse_loot_embed(author, &embed.description, &fields) {
+                Ok(parsed) => {
+                    for mut drop in parsed {
+                        drop.ingest = IngestPath::Webhook;
+
+                        println!(
+                            "Processing drop: User: {}, Source: {}, Items: {:?}",
+                            drop.user, drop.source, drop.loots
+                        );
+
+                        drops.push(drop);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Failed to parse loot text: {}", e);
+                    post_raw_embed(http, data, payload, &embed.description).await;
+                }
+            }
+        }
+    }
+
+    process_payload_drops(http, data, payload, drops).await
+}
+
+/// Runs drops built from a payload through the world and clan policies, then
+/// `process_drop`
+async fn process_payload_drops(
+    http: &serenity::Http,
+    data: &Data,
+    payload: &WebhookPayload,
+    drops: Vec<DinkDrop>,
+) -> Result<Vec<DropOutcome>, Error> {
+    // Check where the drop came from before it reaches any team, so drops
+    // from outside the event clans are refused even under a registered name
+    let mut policy = data.world_policy.check(payload);
+    if policy.is_ok() {
+        if let Err(reason) = data.clan_policy.check(payload) {
+            let username = payload.playerName.to_lowercase();
+            if !database::is_clan_guest(&data.database, &username).await? {
+                policy = Err(reason);
+            }
+        }
+    }
+
+    let mut outcomes = Vec::new();
+    for mut drop in drops {
+        apply_payload_context(&mut drop, payload);
+
+        let outcome = match &policy {
+            Ok(()) => dink::process_drop(http, data, drop).await?,
+            Err(reason) => {
+                let outcome = dink::reject(data, &drop, reason.clone()).await?;
+                data.metrics.drop_outcome(&outcome);
+                let event = dink::events::DropEvent::new(&drop).finish(&outcome);
+                data.drop_events.publish(event);
+                outcome
+            }
+        };
+        outcomes.push(outcome);
+    }
+
+    Ok(outcomes)
+}
+
+/// Copies the account hash and event time from the payload onto a drop.
+/// Without a Dink timestamp the drop is dated when the payload was received,
+/// so replaying it from the queue later doesn't change its time.
+fn apply_payload_context(drop: &mut DinkDrop, payload: &WebhookPayload) {
+    drop.account_hash = Some(payload.dinkAccountHash.clone());
+    drop.queue_id = payload.queue_id;
+
+    let timestamp = payload
+        .embeds
+        .iter()
+        .filter_map(|embed| embed.timestamp.as_deref())
+        .find_map(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+        .or_else(|| {
+            payload
+                .received_at
+                .and_then(|received_at| DateTime::from_timestamp(received_at, 0))
+        });
+
+    if let Some(timestamp) = timestamp {
+        drop.timestamp = timestamp;
+    }
+}
+
+async fn on_pet(
+    http: &serenity::Http,
+    data: &Data,
+    payload: &WebhookPayload,
+    extra: PetExtra,
+) -> Result<Vec<DropOutcome>, Error> {
+    println!(
+        "{} got a pet: {} (duplicate: {})",
+        payload.playerName,
+        extra.pet_name.as_deref().unwrap_or("unknown"),
+        extra.duplicate
+    );
+    post_raw_event(http, data, payload).await?;
+    Ok(Vec::new())
+}
+
+async fn on_collection(
+    http: &serenity::Http,
+    data: &Data,
+    payload: &WebhookPayload,
+    extra: CollectionExtra,
+) -> Result<Vec<DropOutcome>, Error> {
+    println!(
+        "{} logged a new collection item: {}",
+        payload.playerName, extra.item_name
+    );
+    post_raw_event(http, data, payload).await?;
+    Ok(Vec::new())
+}
+
+async fn on_level(
+    http: &serenity::Http,
+    data: &Data,
+    payload: &WebhookPayload,
+    extra: LevelExtra,
+) -> Result<Vec<DropOutcome>, Error> {
+    println!(
+        "{} levelled up: {:?}",
+        payload.playerName, extra.levelled_skills
+    );
+    post_raw_event(http, data, payload).await?;
+    Ok(Vec::new())
+}
+
+async fn on_kill_count(
+    http: &serenity::Http,
+    data: &Data,
+    payload: &WebhookPayload,
+    extra: KillCountExtra,
+) -> Result<Vec<DropOutcome>, Error> {
+    println!(
+        "{} reached {} kills of {}",
+        payload.playerName, extra.count, extra.boss
+    );
+    post_raw_event(http, data, payload).await?;
+    Ok(Vec::new())
+}
+
+async fn on_clue(
+    http: &serenity::Http,
+    data: &Data,
+    payload: &WebhookPayload,
+    extra: ClueExtra,
+) -> Result<Vec<DropOutcome>, Error> {
+    println!(
+        "{} completed a {} clue ({} total)",
+        payload.playerName, extra.clue_type, extra.number_completed
+    );
+    post_raw_event(http, data, payload).await?;
+
+    if extra.items.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Casket loot is credited like any other drop, gated by `source_access`
+    let drop = DinkDrop::from_clue_extra(&payload.playerName, &extra);
+    process_payload_drops(http, data, payload, vec![drop]).await
+}
+
+async fn on_slayer(
+    http: &serenity::Http,
+    data: &Data,
+    payload: &WebhookPayload,
+    extra: SlayerExtra,
+) -> Result<Vec<DropOutcome>, Error> {
+    println!(
+        "{} completed a slayer task: {}",
+        payload.playerName, extra.slayer_task
+    );
+    post_raw_event(http, data, payload).await?;
+    Ok(Vec::new())
+}
+
+async fn on_combat_achievement(
+    http: &serenity::Http,
+    data: &Data,
+    payload: &WebhookPayload,
+    extra: CombatAchievementExtra,
+) -> Result<Vec<DropOutcome>, Error> {
+    println!(
+        "{} completed the {} combat achievement: {}",
+        payload.playerName, extra.tier, extra.task
+    );
+    post_raw_event(http, data, payload).await?;
+    Ok(Vec::new())
+}
+
+async fn on_death(
+    http: &serenity::Http,
+    data: &Data,
+    payload: &WebhookPayload,
+    extra: DeathExtra,
+) -> Result<Vec<DropOutcome>, Error> {
+    println!(
+        "{} died (pvp: {}, value lost: {})",
+        payload.playerName, extra.is_pvp, extra.value_lost
+    );
+    post_raw_event(http, data, payload).await?;
+    Ok(Vec::new())
+}
+
+/// Posts the raw embed contents of an event into the dink channel
+async fn post_raw_event(
+    http: &serenity::Http,
+    data: &Data,
+    payload: &WebhookPayload,
+) -> Result<(), Error> {
+    for embed in &payload.embeds {
+        post_raw_embed(http, data, payload, &embed.description).await;
+    }
+
+    Ok(())
+}
+
+/// Sends a simple message with the raw embed info
+async fn post_raw_embed(
+    http: &serenity::Http,
+    data: &Data,
+    payload: &WebhookPayload,
+    description: &str,
+) {
+    let channel_id = serenity::ChannelId::new(data.dink_channel_id);
+    let message = format!(
+        "**New Event from {}**\nType: {}\n\n{}",
+        payload.playerName, payload.r#type, description
+    );
+
+    if let Err(send_err) = channel_id.say(http, message).await {
+        data.metrics.discord_error();
+        eprintln!("Error sending message: {}", send_err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(kind: &str, extra: serde_json::Value) -> WebhookPayload {
+        serde_json::from_value(serde_json::json!({
+            "type": kind,
+            "playerName": "Solo H",
+            "accountType": "NORMAL",
+            "dinkAccountHash": "abc123",
+            "clanName": null,
+            "seasonalWorld": false,
+            "world": 302,
+            "regionId": 12850,
+            "extra": extra,
+            "embeds": []
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_loot_notification() {
+        let payload = payload(
+            "LOOT",
+            serde_json::json!({
+                "items": [
+                    { "id": 526, "quantity": 1, "priceEach": 62, "name": "Bones" },
+                    { "id": 995, "quantity": 15, "priceEach": 1, "name": "Coins" }
+                ],
+                "source": "Man",
+                "category": "NPC",
+                "killCount": 12
+            }),
+        );
+
+        match DinkNotification::from_payload(&payload).unwrap() {
+            DinkNotification::Loot(extra) => {
+                assert_eq!(extra.source, "Man");
+                assert_eq!(extra.items.len(), 2);
+                assert_eq!(extra.items[0].id, 526);
+                assert_eq!(extra.items[1].price_each, 1);
+                assert_eq!(extra.kill_count, Some(12));
+            }
+            other => panic!("expected loot notification, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_typed_notifications() {
+        let level = payload(
+            "LEVEL",
+            serde_json::json!({ "levelledSkills": { "Attack": 70 }, "allSkills": { "Attack": 70 } }),
+        );
+        assert!(matches!(
+            DinkNotification::from_payload(&level).unwrap(),
+            DinkNotification::Level(extra) if extra.levelled_skills["Attack"] == 70
+        ));
+
+        let slayer = payload(
+            "SLAYER",
+            serde_json::json!({ "slayerTask": "Kurask", "slayerCompleted": "30", "slayerPoints": "15" }),
+        );
+        assert!(matches!(
+            DinkNotification::from_payload(&slayer).unwrap(),
+            DinkNotification::Slayer(extra) if extra.slayer_task == "Kurask"
+        ));
+
+        let unknown = payload("CHAT", serde_json::json!({}));
+        assert!(matches!(
+            DinkNotification::from_payload(&unknown).unwrap(),
+            DinkNotification::Other(kind) if kind == "CHAT"
+        ));
+
+        let malformed = payload(
+            "LOOT",
+            serde_json::json!({ "items": "Bones", "source": "Man" }),
+        );
+        assert!(DinkNotification::from_payload(&malformed).is_err());
+    }
+
+    #[test]
+    fn test_clue_drop() {
+        let payload = payload(
+            "CLUE",
+            serde_json::json!({
+                "clueType": "Hard",
+                "numberCompleted": 12,
+                "items": [
+                    { "id": 1079, "quantity": 1, "priceEach": 4000, "name": "Rune platelegs" }
+                ]
+            }),
+        );
+        let DinkNotification::Clue(extra) = DinkNotification::from_payload(&payload).unwrap()
+        else {
+            panic!("expected a clue notification");
+        };
+
+        let drop = DinkDrop::from_clue_extra(&payload.playerName, &extra);
+        assert_eq!(drop.source, "Reward casket (hard)");
+        assert_eq!(drop.loots, vec![("Rune platelegs".to_string(), 1)]);
+
+        // The casket name reaches the `source_access` gate
+        let town_config = crate::coc::buildings::init_assets().unwrap();
+        assert!(town_config.get_source_access(&drop.source).is_some());
+    }
+
+    #[test]
+    fn test_payload_timestamp() {
+        let mut payload = payload("LOOT", serde_json::json!({}));
+        payload.received_at = Some(1_700_000_000);
+
+        // Without a Dink timestamp the drop keeps the time it was received
+        let mut drop = DinkDrop::new("Solo H".to_string(), "Man".to_string(), Vec::new());
+        apply_payload_context(&mut drop, &payload);
+        assert_eq!(drop.timestamp.timestamp(), 1_700_000_000);
+
+        payload.embeds = serde_json::from_value(serde_json::json!([{
+            "title": "Loot Drop",
+            "description": "",
+            "timestamp": "2023-11-14T22:00:00Z"
+        }]))
+        .unwrap();
+        apply_payload_context(&mut drop, &payload);
+        assert_eq!(drop.timestamp.timestamp(), 1_699_999_200);
+    }
+}
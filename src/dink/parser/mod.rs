@@ -0,0 +1,599 @@
+use std::fmt;
+
+use super::DinkDrop;
+
+/// Longest player name accepted, counted in characters rather than bytes
+pub const MAX_USERNAME_CHARS: usize = 15;
+
+const HEADER: &str = " has looted:";
+const SOURCE_PREFIX: &str = "From:";
+
+/// Why a Dink loot message could not be read
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// Loot appeared before any "<player> has looted:" line
+    MissingHeader,
+    EmptyUsername,
+    UsernameTooLong(String),
+    InvalidQuantity(String),
+    EmptyItemName(String),
+    /// A `[name](url)` link whose brackets or parentheses never close
+    UnterminatedLink(String),
+    MissingSource,
+    NoLoot,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::MissingHeader => write!(f, "Could not find username in loot text"),
+            ParseError::EmptyUsername => write!(f, "Username is empty"),
+            ParseError::UsernameTooLong(name) => write!(
+                f,
+                "Username '{}' is too long (exceeds {} characters)",
+                name, MAX_USERNAME_CHARS
+            ),
+            ParseError::InvalidQuantity(quantity) => {
+                write!(f, "Invalid item quantity '{}'", quantity)
+            }
+            ParseError::EmptyItemName(line) => write!(f, "Missing item name in '{}'", line),
+            ParseError::UnterminatedLink(text) => write!(f, "Unterminated link in '{}'", text),
+            ParseError::MissingSource => write!(f, "Could not find loot source in loot text"),
+            ParseError::NoLoot => write!(f, "No loots found in loot text"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parses a Dink loot embed into one drop per looting player.
+///
+/// The description follows Dink's loot template:
+///
+/// ```text
+/// <player> has looted:
+/// <quantity> x [<item>](<url>) (<value>)
+/// From: [<source>](<url>)
+/// ```
+///
+/// Group loot repeats the header line for each player in the group, with
+/// a single shared "From:" line. Rich-embed layouts may instead list the
+/// items and source in embed fields, and name the player as the author.
+pub fn parse_loot_embed(
+    author: Option<&str>,
+    description: &str,
+    fields: &[(&str, &str)],
+) -> Result<Vec<DinkDrop>, ParseError> {
+    let mut state = State::default();
+
+    for line in description.lines() {
+        state.line(author, line)?;
+    }
+
+    for (name, value) in fields {
+        let name = name.trim().trim_end_matches(':').trim();
+        if name.eq_ignore_ascii_case("from") || name.eq_ignore_ascii_case("source") {
+            state.source = Some(parse_name(value)?);
+            continue;
+        }
+
+        for line in value.lines() {
+            state.line(author, line)?;
+        }
+    }
+
+    let source = match state.source {
+        Some(source) if !source.is_empty() => source,
+        _ => return Err(ParseError::MissingSource),
+    };
+
+    let drops: Vec<DinkDrop> = state
+        .players
+        .into_iter()
+        .filter(|(_, loots)| !loots.is_empty())
+        .map(|(user, loots)| DinkDrop::new(user, source.clone(), loots))
+        .collect();
+
+    if drops.is_empty() {
+        return Err(ParseError::NoLoot);
+    }
+
+    Ok(drops)
+}
+
+#[derive(Default)]
+struct State {
+    /// Players in the order they appear, each with the loot listed under them
+    players: Vec<(String, Vec<(String, u32)>)>,
+    source: Option<String>,
+}
+
+impl State {
+    fn line(&mut self, author: Option<&str>, line: &str) -> Result<(), ParseError> {
+        let line = strip_bullet(line.trim());
+
+        if let Some((name, rest)) = line.split_once(HEADER) {
+            let user = parse_username(name)?;
+            self.player(&user);
+            return self.line(author, rest);
+        }
+
+        if let Some(rest) = line.strip_prefix(SOURCE_PREFIX) {
+            self.source = Some(parse_name(rest)?);
+            return Ok(());
+        }
+
+        let Some(loot) = parse_loot_line(line)? else {
+            return Ok(());
+        };
+
+        if self.players.is_empty() {
+            let user = parse_username(author.ok_or(ParseError::MissingHeader)?)?;
+            self.player(&user);
+        }
+
+        if let Some((_, loots)) = self.players.last_mut() {
+            loots.push(loot);
+        }
+
+        Ok(())
+    }
+
+    /// Makes `user` the player that following loot lines belong to
+    fn player(&mut self, user: &str) {
+        match self.players.iter().position(|(name, _)| name == user) {
+            Some(index) => {
+                let entry = self.players.remove(index);
+                self.players.push(entry);
+            }
+            None => self.players.push((user.to_string(), Vec::new())),
+        }
+    }
+}
+
+fn strip_bullet(line: &str) -> &str {
+    line.strip_prefix("- ")
+        .or_else(|| line.strip_prefix("• "))
+        .unwrap_or(line)
+        .trim_start()
+}
+
+/// Removes markdown bold around a name, e.g. `**Solo H**`
+fn strip_emphasis(text: &str) -> &str {
+    text.trim().trim_matches('*').trim()
+}
+
+fn parse_username(text: &str) -> Result<String, ParseError> {
+    // RuneLite reports spaces in player names as non-breaking spaces
+    let name = parse_name(&text.replace('\u{a0}', " "))?;
+
+    if name.is_empty() {
+        return Err(ParseError::EmptyUsername);
+    }
+
+    if name.chars().count() > MAX_USERNAME_CHARS {
+        return Err(ParseError::UsernameTooLong(name));
+    }
+
+    Ok(name)
+}
+
+/// Reads a name that is either a markdown link or plain text
+fn parse_name(text: &str) -> Result<String, ParseError> {
+    let text = strip_emphasis(text);
+
+    if text.starts_with('[') {
+        let (name, _) = parse_link(text)?;
+        return Ok(name.trim().to_string());
+    }
+
+    Ok(text.to_string())
+}
+
+/// Reads `<quantity> x <item> (<value>)`, returning `None` for lines that
+/// aren't loot at all
+fn parse_loot_line(line: &str) -> Result<Option<(String, u32)>, ParseError> {
+    let Some((quantity, item)) = line.split_once(" x ") else {
+        return Ok(None);
+    };
+    let quantity = quantity.trim();
+    let item = item.trim();
+
+    let looks_numeric = !quantity.is_empty()
+        && quantity.chars().all(|c| {
+            c.is_ascii_digit() || matches!(c, ',' | '.' | 'k' | 'K' | 'm' | 'M' | 'b' | 'B')
+        })
+        && quantity.starts_with(|c: char| c.is_ascii_digit());
+
+    // Plain sentences can contain " x "; only linked items are certainly loot
+    if !looks_numeric {
+        return match item.starts_with('[') {
+            true => Err(ParseError::InvalidQuantity(quantity.to_string())),
+            false => Ok(None),
+        };
+    }
+
+    let quantity = parse_quantity(quantity)?;
+
+    let name = if item.starts_with('[') {
+        parse_link(item)?.0
+    } else {
+        // Drop the trailing " (value)" from plain item names, keeping
+        // suffixes that are part of the name such as "Onyx bolts (e)"
+        match item.rfind(" (") {
+            Some(index) if item.ends_with(')') && is_value(&item[index + 2..item.len() - 1]) => {
+                item[..index].to_string()
+            }
+            _ => item.to_string(),
+        }
+    };
+
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(ParseError::EmptyItemName(line.to_string()));
+    }
+
+    Ok(Some((name.to_string(), quantity)))
+}
+
+/// Whether the text is an item value, like `62`, `0` or `1.2K`
+fn is_value(text: &str) -> bool {
+    text == "0" || parse_quantity(text).is_ok()
+}
+
+/// Parses quantities such as `15`, `1,000`, `1.5K` and `2M`
+pub fn parse_quantity(text: &str) -> Result<u32, ParseError> {
+    let invalid = || ParseError::InvalidQuantity(text.to_string());
+
+    let (number, multiplier, max_decimals) = match text.chars().last() {
+        Some('k' | 'K') => (&text[..text.len() - 1], 1_000u64, 3),
+        Some('m' | 'M') => (&text[..text.len() - 1], 1_000_000, 6),
+        Some('b' | 'B') => (&text[..text.len() - 1], 1_000_000_000, 9),
+        _ => (text, 1, 0),
+    };
+
+    let (whole, decimals) = match number.split_once('.') {
+        Some((whole, decimals)) => (whole, decimals),
+        None => (number, ""),
+    };
+
+    if decimals.len() > max_decimals || (number.contains('.') && decimals.is_empty()) {
+        return Err(invalid());
+    }
+
+    // Thousands separators must group exactly three digits
+    let mut groups = whole.split(',');
+    let first = groups.next().unwrap_or("");
+    let grouped = whole.contains(',');
+    if first.is_empty() || (grouped && first.len() > 3) {
+        return Err(invalid());
+    }
+
+    let mut digits = first.to_string();
+    for group in groups {
+        if group.len() != 3 {
+            return Err(invalid());
+        }
+        digits.push_str(group);
+    }
+
+    if !digits.chars().all(|c| c.is_ascii_digit()) || !decimals.chars().all(|c| c.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+
+    let whole: u64 = digits.parse().map_err(|_| invalid())?;
+    let fraction: u64 = match decimals.is_empty() {
+        true => 0,
+        false => {
+            let scale = 10u64.pow((max_decimals - decimals.len()) as u32);
+            decimals.parse::<u64>().map_err(|_| invalid())? * scale
+        }
+    };
+
+    let quantity = whole
+        .checked_mul(multiplier)
+        .and_then(|value| value.checked_add(fraction))
+        .ok_or_else(invalid)?;
+
+    match u32::try_from(quantity) {
+        Ok(quantity) if quantity > 0 => Ok(quantity),
+        _ => Err(invalid()),
+    }
+}
+
+/// Splits `[name](url) rest` into the unescaped name and `rest`, matching
+/// nested brackets in the name and parentheses in the url
+fn parse_link(text: &str) -> Result<(String, &str), ParseError> {
+    let unterminated = || ParseError::UnterminatedLink(text.to_string());
+
+    let mut name = String::new();
+    let mut depth = 0;
+    let mut name_end = None;
+    let mut chars = text.char_indices();
+
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some((_, escaped)) = chars.next() {
+                    name.push(escaped);
+                }
+                continue;
+            }
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    name_end = Some(index + 1);
+                    break;
+                }
+            }
+            _ => {}
+        }
+
+        if depth > 1 || (depth == 1 && c != '[') {
+            name.push(c);
+        }
+    }
+
+    let after = &text[name_end.ok_or_else(unterminated)?..];
+
+    let Some(url) = after.strip_prefix('(') else {
+        return Ok((name, after));
+    };
+
+    let mut depth = 1;
+    for (index, c) in url.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok((name, &url[index + 1..]));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Err(unterminated())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn parse_loot_text(text: &str) -> Result<Vec<DinkDrop>, ParseError> {
+        parse_loot_embed(None, text, &[])
+    }
+
+    const WIKI: &str = "https://oldschool.runescape.wiki/w/Special:Search?search=";
+
+    /// Loot messages as posted by Dink's default templates
+    const SAMPLES: &[&str] = &[
+        "Solo H has looted: \n\n1 x [Bones](https://oldschool.runescape.wiki/w/Special:Search?search=Bones) (62)\n15 x [Coins](https://oldschool.runescape.wiki/w/Special:Search?search=Coins) (15)\nFrom: [Man](https://oldschool.runescape.wiki/w/Special:Search?search=Man)",
+        "Zezima has looted: \n\n1 x [Twisted bow](https://oldschool.runescape.wiki/w/Special:Search?search=Twisted+bow) (1.2B)\n1,500 x [Dragon arrow](https://oldschool.runescape.wiki/w/Special:Search?search=Dragon+arrow) (2.1M)\nFrom: [Chambers of Xeric](https://oldschool.runescape.wiki/w/Special:Search?search=Chambers+of+Xeric)",
+        "Iron\u{a0}Man has looted: \n\n1 x [Amulet of glory(4)](https://oldschool.runescape.wiki/w/Special:Search?search=Amulet+of+glory%284%29) (12.5K)\n1.5K x [Coins](https://oldschool.runescape.wiki/w/Special:Search?search=Coins) (1.5K)\nFrom: [Lunar Chest](https://oldschool.runescape.wiki/w/Special:Search?search=Lunar+Chest)",
+        "Lynx Titan has looted: \n\n2 x [Onyx bolts (e)](https://oldschool.runescape.wiki/w/Special:Search?search=Onyx+bolts+%28e%29) (19.6K)\nRaider Two has looted: \n\n1 x [Avernic defender hilt](https://oldschool.runescape.wiki/w/Special:Search?search=Avernic+defender+hilt) (45M)\nFrom: [Theatre of Blood](https://oldschool.runescape.wiki/w/Special:Search?search=Theatre+of+Blood)",
+    ];
+
+    fn link(name: &str) -> String {
+        let query: String = name
+            .chars()
+            .map(|c| match c {
+                ' ' => "+".to_string(),
+                '(' => "%28".to_string(),
+                ')' => "%29".to_string(),
+                '[' => "%5B".to_string(),
+                ']' => "%5D".to_string(),
+                c => c.to_string(),
+            })
+            .collect();
+        format!("[{}]({}{})", name, WIKI, query)
+    }
+
+    #[test]
+    fn test_parse_samples() {
+        let drops = parse_loot_text(SAMPLES[1]).unwrap();
+        assert_eq!(drops.len(), 1);
+        assert_eq!(drops[0].source, "Chambers of Xeric");
+        assert_eq!(
+            drops[0].loots,
+            vec![
+                ("Twisted bow".to_string(), 1),
+                ("Dragon arrow".to_string(), 1500)
+            ]
+        );
+
+        let drops = parse_loot_text(SAMPLES[2]).unwrap();
+        assert_eq!(drops[0].user, "Iron Man");
+        assert_eq!(
+            drops[0].loots,
+            vec![
+                ("Amulet of glory(4)".to_string(), 1),
+                ("Coins".to_string(), 1500)
+            ]
+        );
+
+        // Group loot yields a drop per player from the shared source
+        let drops = parse_loot_text(SAMPLES[3]).unwrap();
+        assert_eq!(drops.len(), 2);
+        assert_eq!(drops[0].user, "Lynx Titan");
+        assert_eq!(drops[0].loots, vec![("Onyx bolts (e)".to_string(), 2)]);
+        assert_eq!(drops[1].user, "Raider Two");
+        assert_eq!(drops[1].source, "Theatre of Blood");
+    }
+
+    #[test]
+    fn test_parse_rich_embed() {
+        let fields = [
+            ("Loot", "1 x [Bones](https://x/Bones)\n2 x Coins (2)"),
+            ("Total Value", "64 gp"),
+            ("Source", "[Man](https://x/Man)"),
+        ];
+
+        let drops = parse_loot_embed(Some("Solo H"), "", &fields).unwrap();
+        assert_eq!(drops[0].user, "Solo H");
+        assert_eq!(drops[0].source, "Man");
+        assert_eq!(
+            drops[0].loots,
+            vec![("Bones".to_string(), 1), ("Coins".to_string(), 2)]
+        );
+    }
+
+    #[test]
+    fn test_plain_item_suffixes() {
+        let loot = |line| parse_loot_line(line).unwrap().unwrap();
+
+        assert_eq!(
+            loot("2 x Onyx bolts (e)"),
+            ("Onyx bolts (e)".to_string(), 2)
+        );
+        assert_eq!(
+            loot("2 x Onyx bolts (e) (19.6K)"),
+            ("Onyx bolts (e)".to_string(), 2)
+        );
+        assert_eq!(
+            loot("1 x Amulet of glory(4)"),
+            ("Amulet of glory(4)".to_string(), 1)
+        );
+        assert_eq!(
+            loot("1 x Amulet of glory(4) (12.5K)"),
+            ("Amulet of glory(4)".to_string(), 1)
+        );
+        assert_eq!(
+            loot("1 x Abyssal whip (0)"),
+            ("Abyssal whip".to_string(), 1)
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse_loot_text("1 x [Bones](u)\nFrom: Man").err(),
+            Some(ParseError::MissingHeader)
+        );
+        assert_eq!(
+            parse_loot_text("A has looted:\nlots x [Bones](u)\nFrom: Man").err(),
+            Some(ParseError::InvalidQuantity("lots".to_string()))
+        );
+        assert_eq!(
+            parse_loot_text("A has looted:\n1 x [Bones(u)\nFrom: Man").err(),
+            Some(ParseError::UnterminatedLink("[Bones(u)".to_string()))
+        );
+        assert_eq!(
+            parse_loot_text("A has looted:\n1 x [Bones](u)").err(),
+            Some(ParseError::MissingSource)
+        );
+        assert_eq!(
+            parse_loot_text("A has looted:\nFrom: Man").err(),
+            Some(ParseError::NoLoot)
+        );
+        assert!(matches!(
+            parse_loot_text("Sixteen chars xx has looted:\n1 x [Bones](u)\nFrom: Man"),
+            Err(ParseError::UsernameTooLong(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_quantity() {
+        assert_eq!(parse_quantity("1,000"), Ok(1000));
+        assert_eq!(parse_quantity("1,234,567"), Ok(1234567));
+        assert_eq!(parse_quantity("12K"), Ok(12000));
+        assert_eq!(parse_quantity("1.5m"), Ok(1500000));
+        assert!(parse_quantity("1,00").is_err());
+        assert!(parse_quantity("1.5").is_err());
+        assert!(parse_quantity("0").is_err());
+        assert!(parse_quantity("5B").is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn quantity_roundtrip(quantity in 1u32..) {
+            let plain = quantity.to_string();
+            let mut grouped = String::new();
+            for (index, c) in plain.chars().enumerate() {
+                if index > 0 && (plain.len() - index) % 3 == 0 {
+                    grouped.push(',');
+                }
+                grouped.push(c);
+            }
+
+            prop_assert_eq!(parse_quantity(&plain), Ok(quantity));
+            prop_assert_eq!(parse_quantity(&grouped), Ok(quantity));
+        }
+
+        #[test]
+        fn abbreviated_quantity(quantity in 1u32..4_000_000) {
+            let thousands = format!("{}.{:03}K", quantity / 1_000, quantity % 1_000);
+            let millions = format!("{}.{:06}M", quantity / 1_000_000, quantity % 1_000_000);
+
+            prop_assert_eq!(parse_quantity(&thousands), Ok(quantity));
+            prop_assert_eq!(parse_quantity(&millions), Ok(quantity));
+        }
+
+        #[test]
+        fn loot_roundtrip(
+            user in "[A-Za-z0-9][A-Za-z0-9 \u{a0}_-]{0,11}",
+            items in prop::collection::vec(
+                ("[A-Za-z][A-Za-z0-9 '()+-]{0,20}( \\[[a-z]{1,4}\\])?", 1u32..),
+                1..6,
+            ),
+            source in "[A-Za-z][A-Za-z ']{0,20}",
+        ) {
+            let mut text = format!("{} has looted: \n\n", user);
+            for (name, quantity) in &items {
+                text.push_str(&format!("{} x {} (1.2K)\n", quantity, link(name)));
+            }
+            text.push_str(&format!("From: {}", link(&source)));
+
+            let drops = parse_loot_text(&text).unwrap();
+            let expected: Vec<(String, u32)> = items
+                .iter()
+                .map(|(name, quantity)| (name.trim().to_string(), *quantity))
+                .collect();
+
+            prop_assert_eq!(drops.len(), 1);
+            prop_assert_eq!(&drops[0].user, &user.replace('\u{a0}', " ").trim().to_string());
+            prop_assert_eq!(&drops[0].source, source.trim());
+            prop_assert_eq!(&drops[0].loots, &expected);
+        }
+
+        #[test]
+        fn multibyte_usernames(user in "\\PC{1,15}") {
+            let user = user.replace('\u{a0}', " ");
+            let user = user.trim();
+            prop_assume!(!user.is_empty() && !user.contains(['[', '*', '\\']));
+            let header = format!("{} has looted:", user);
+            prop_assume!(!user.contains(" x ") && !header.starts_with("- ") && !header.starts_with("• "));
+
+            let text = format!("{}\n1 x {}\nFrom: Man", header, link("Bones"));
+            let drops = parse_loot_text(&text).unwrap();
+            prop_assert_eq!(&drops[0].user, user);
+        }
+
+        /// Arbitrary input must produce a drop or an error, never a panic
+        #[test]
+        fn fuzz_arbitrary_text(text in "\\PC*") {
+            let _ = parse_loot_text(&text);
+        }
+
+        /// Real samples cut or spliced at arbitrary points
+        #[test]
+        fn fuzz_mutated_samples(
+            sample in 0..SAMPLES.len(),
+            cut in any::<prop::sample::Index>(),
+            insert in "[\\[\\]() x,.KM0-9\n:\\\\]{0,8}",
+        ) {
+            let sample = SAMPLES[sample];
+            let mut at = cut.index(sample.len() + 1);
+            while !sample.is_char_boundary(at) {
+                at -= 1;
+            }
+
+            let _ = parse_loot_text(&sample[..at]);
+            let _ = parse_loot_text(&format!("{}{}{}", &sample[..at], insert, &sample[at..]));
+        }
+    }
+}
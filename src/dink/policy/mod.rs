@@ -0,0 +1,206 @@
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::dink::RejectReason;
+use crate::webhook::WebhookPayload;
+
+/// An allow and deny list of numeric IDs
+#[derive(Debug, Default, Deserialize)]
+pub struct IdFilter {
+    #[serde(default)]
+    pub allow: Vec<i32>,
+    #[serde(default)]
+    pub deny: Vec<i32>,
+}
+
+impl IdFilter {
+    /// Denied IDs always fail; an empty allow list accepts everything else
+    pub fn permits(&self, id: i32) -> bool {
+        if self.deny.contains(&id) {
+            return false;
+        }
+
+        self.allow.is_empty() || self.allow.contains(&id)
+    }
+}
+
+/// Which worlds and regions webhook drops are accepted from
+#[derive(Debug, Default, Deserialize)]
+pub struct WorldPolicy {
+    #[serde(default)]
+    pub allow_seasonal: bool,
+    #[serde(default)]
+    pub worlds: IdFilter,
+    #[serde(default)]
+    pub regions: IdFilter,
+}
+
+impl WorldPolicy {
+    /// Load the policy from a TOML file
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)?;
+        let policy: WorldPolicy = toml::from_str(&content)?;
+        Ok(policy)
+    }
+
+    /// Checks where a payload was sent from
+    pub fn check(&self, payload: &WebhookPayload) -> Result<(), RejectReason> {
+        if payload.seasonalWorld && !self.allow_seasonal {
+            return Err(RejectReason::SeasonalWorld);
+        }
+
+        if !self.worlds.permits(payload.world) {
+            return Err(RejectReason::World(payload.world));
+        }
+
+        if !self.regions.permits(payload.regionId) {
+            return Err(RejectReason::Region(payload.regionId));
+        }
+
+        Ok(())
+    }
+}
+
+/// Which clans webhook drops are accepted from
+#[derive(Debug, Default, Deserialize)]
+pub struct ClanPolicy {
+    #[serde(default)]
+    pub allowed: Vec<String>,
+}
+
+// Raw structure of the event config, of which only the clan section is used here
+#[derive(Debug, Deserialize)]
+struct EventConfigRaw {
+    #[serde(default)]
+    clan: ClanPolicy,
+}
+
+impl ClanPolicy {
+    /// Load the clan section of the event config
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)?;
+        let raw: EventConfigRaw = toml::from_str(&content)?;
+        Ok(raw.clan)
+    }
+
+    /// Checks the clan a payload was sent from. Guests are let in by the
+    /// caller, since the override list lives in the database.
+    pub fn check(&self, payload: &WebhookPayload) -> Result<(), RejectReason> {
+        if self.allowed.is_empty() {
+            return Ok(());
+        }
+
+        let clan = payload.clanName.as_deref().map(str::trim).unwrap_or("");
+        if self
+            .allowed
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(clan))
+        {
+            return Ok(());
+        }
+
+        Err(RejectReason::Clan(clan.to_string()))
+    }
+}
+
+/// Initialize the world policy
+pub fn init_world_policy() -> Result<WorldPolicy, Box<dyn std::error::Error>> {
+    WorldPolicy::load_from_file("config/world_policy.toml")
+}
+
+/// Initialize the clan policy
+pub fn init_clan_policy() -> Result<ClanPolicy, Box<dyn std::error::Error>> {
+    ClanPolicy::load_from_file("config/event.toml")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(seasonal: bool, world: i32, region: i32) -> WebhookPayload {
+        clan_payload(None, seasonal, world, region)
+    }
+
+    fn clan_payload(clan: Option<&str>, seasonal: bool, world: i32, region: i32) -> WebhookPayload {
+        serde_json::from_value(serde_json::json!({
+            "type": "LOOT",
+            "playerName": "Zezima",
+            "accountType": null,
+            "dinkAccountHash": "abc",
+            "clanName": clan,
+            "seasonalWorld": seasonal,
+            "world": world,
+            "regionId": region,
+            "extra": {},
+            "embeds": []
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_world_policy() {
+        let policy = WorldPolicy {
+            allow_seasonal: false,
+            worlds: IdFilter {
+                allow: vec![],
+                deny: vec![345],
+            },
+            regions: IdFilter {
+                allow: vec![12850, 12851],
+                deny: vec![12851],
+            },
+        };
+
+        assert_eq!(policy.check(&payload(false, 302, 12850)), Ok(()));
+        assert_eq!(
+            policy.check(&payload(true, 302, 12850)),
+            Err(RejectReason::SeasonalWorld)
+        );
+        assert_eq!(
+            policy.check(&payload(false, 345, 12850)),
+            Err(RejectReason::World(345))
+        );
+        assert_eq!(
+            policy.check(&payload(false, 302, 12851)),
+            Err(RejectReason::Region(12851))
+        );
+        assert_eq!(
+            policy.check(&payload(false, 302, 1)),
+            Err(RejectReason::Region(1))
+        );
+    }
+
+    #[test]
+    fn test_clan_policy() {
+        let open = ClanPolicy::default();
+        assert_eq!(open.check(&payload(false, 302, 1)), Ok(()));
+
+        let policy = ClanPolicy {
+            allowed: vec!["Iron Foundry".to_string()],
+        };
+        assert_eq!(
+            policy.check(&clan_payload(Some("iron foundry"), false, 302, 1)),
+            Ok(())
+        );
+        assert_eq!(
+            policy.check(&clan_payload(Some("Other Clan"), false, 302, 1)),
+            Err(RejectReason::Clan("Other Clan".to_string()))
+        );
+        assert_eq!(
+            policy.check(&payload(false, 302, 1)),
+            Err(RejectReason::Clan(String::new()))
+        );
+    }
+
+    #[test]
+    fn test_load_world_policy() {
+        let result = init_world_policy();
+        assert!(
+            result.is_ok(),
+            "Failed to load world policy: {:?}",
+            result.err()
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use sqlx::SqlitePool;
+
+use super::RejectReason;
+use crate::coc::database;
+use crate::Error;
+
+/// When drops count towards the event
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EventSchedule {
+    pub starts_at: Option<i64>,
+    pub ends_at: Option<i64>,
+    /// Pauses as (paused_at, resumed_at), an ongoing pause has no resumed_at
+    pub pauses: Vec<(i64, Option<i64>)>,
+}
+
+impl EventSchedule {
+    pub async fn load(pool: &SqlitePool) -> Result<Self, Error> {
+        let (starts_at, ends_at) = database::get_event_window(pool).await?.unwrap_or_default();
+        let pauses = database::get_event_pauses(pool).await?;
+
+        Ok(EventSchedule {
+            starts_at,
+            ends_at,
+            pauses,
+        })
+    }
+
+    /// Checks a drop that happened at `timestamp` falls within the event
+    pub fn check(&self, timestamp: i64) -> Result<(), RejectReason> {
+        if let Some(starts_at) = self.starts_at {
+            if timestamp < starts_at {
+                return Err(RejectReason::EventNotStarted(starts_at));
+            }
+        }
+
+        if let Some(ends_at) = self.ends_at {
+            if timestamp >= ends_at {
+                return Err(RejectReason::EventEnded(ends_at));
+            }
+        }
+
+        let paused = self.pauses.iter().any(|(paused_at, resumed_at)| {
+            timestamp >= *paused_at && resumed_at.is_none_or(|resumed_at| timestamp < resumed_at)
+        });
+        if paused {
+            return Err(RejectReason::EventPaused);
+        }
+
+        Ok(())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.pauses
+            .iter()
+            .any(|(_, resumed_at)| resumed_at.is_none())
+    }
+
+    /// Describes the window for embeds, using Discord timestamps so each
+    /// reader sees their own time zone
+    pub fn describe(&self) -> Option<String> {
+        let window = match (self.starts_at, self.ends_at) {
+            (None, None) => None,
+            (Some(start), None) => Some(format!("Starts <t:{}:f>", start)),
+            (None, Some(end)) => Some(format!("Ends <t:{}:f> (<t:{}:R>)", end, end)),
+            (Some(start), Some(end)) => Some(format!(
+                "<t:{}:f> – <t:{}:f> (ends <t:{}:R>)",
+                start, end, end
+            )),
+        };
+
+        match (window, self.is_paused()) {
+            (Some(window), true) => Some(format!("⏸️ **Paused** • {}", window)),
+            (None, true) => Some("⏸️ **Paused**".to_string()),
+            (window, false) => window,
+        }
+    }
+}
+
+/// Formats a unix timestamp for messages
+pub fn format_time(timestamp: i64) -> String {
+    match DateTime::from_timestamp(timestamp, 0) {
+        Some(time) => time.format("%Y-%m-%d %H:%M UTC").to_string(),
+        None => timestamp.to_string(),
+    }
+}
+
+/// Parses an event time given as RFC 3339, `YYYY-MM-DD HH:MM` or
+/// `YYYY-MM-DD` in UTC, or a unix timestamp
+pub fn parse_time(text: &str) -> Option<i64> {
+    let text = text.trim();
+
+    if let Ok(timestamp) = text.parse::<i64>() {
+        return Some(timestamp);
+    }
+
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Some(time.timestamp());
+    }
+
+    if let Ok(time) = NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M") {
+        return Some(time.and_utc().timestamp());
+    }
+
+    NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|time| time.and_utc().timestamp())
+}
+
+/// The current unix timestamp
+pub fn now() -> i64 {
+    Utc::now().timestamp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_schedule() {
+        let schedule = EventSchedule {
+            starts_at: Some(1_000),
+            ends_at: Some(2_000),
+            pauses: vec![(1_200, Some(1_300)), (1_800, None)],
+        };
+
+        assert_eq!(
+            schedule.check(999),
+            Err(RejectReason::EventNotStarted(1_000))
+        );
+        assert_eq!(schedule.check(1_000), Ok(()));
+        assert_eq!(schedule.check(1_250), Err(RejectReason::EventPaused));
+        assert_eq!(schedule.check(1_300), Ok(()));
+        assert_eq!(schedule.check(1_900), Err(RejectReason::EventPaused));
+        assert_eq!(schedule.check(2_000), Err(RejectReason::EventEnded(2_000)));
+        assert!(schedule.is_paused());
+
+        // Without a window every drop counts
+        assert_eq!(EventSchedule::default().check(0), Ok(()));
+        assert_eq!(EventSchedule::default().describe(), None);
+    }
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time("1700000000"), Some(1_700_000_000));
+        assert_eq!(parse_time("2024-01-01"), Some(1_704_067_200));
+        assert_eq!(parse_time("2024-01-01 12:30"), Some(1_704_112_200));
+        assert_eq!(parse_time("2024-01-01T12:30:00+02:00"), Some(1_704_105_000));
+        assert_eq!(parse_time("next tuesday"), None);
+    }
+}
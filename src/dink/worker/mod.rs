@@ -0,0 +1,269 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use poise::serenity_prelude as serenity;
+use tokio::sync::mpsc;
+
+use crate::coc::database;
+use crate::dink::{self, DropOutcome};
+use crate::webhook::{self, queue, queue::IngestStatus, WebhookPayload};
+use crate::{Data, Error};
+
+/// Queue entries for one team, processed strictly in order
+type LaneSender = mpsc::UnboundedSender<i64>;
+
+/// Queue entries routed to a lane and not finished yet, so the retry sweep
+/// doesn't queue the same entry again while it waits in its lane
+#[derive(Clone, Default)]
+struct InFlight(Arc<Mutex<HashSet<i64>>>);
+
+impl InFlight {
+    /// Marks an entry as in flight, returns false if it already was
+    fn start(&self, id: i64) -> bool {
+        self.0.lock().unwrap().insert(id)
+    }
+
+    fn finish(&self, id: i64) {
+        self.0.lock().unwrap().remove(&id);
+    }
+}
+
+/// Which lane a payload is processed in. Drops from players without a team
+/// share a lane, since they're only rejected or recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum LaneKey {
+    Team(i32),
+    Unassigned,
+}
+
+/// Starts the ingest workers in the background.
+///
+/// The dispatcher resumes entries left over from a previous run, then routes
+/// new and retryable entries to one lane per team, so teams are processed in
+/// parallel while drops for the same team keep their order. Only the REST
+/// client is used, so the workers carry on across gateway reconnects.
+pub fn start(http: Arc<serenity::Http>, data: Arc<Data>, receiver: webhook::WebhookReceiver) {
+    tokio::spawn(async move {
+        let mut dispatcher = Dispatcher {
+            http,
+            data,
+            lanes: HashMap::new(),
+            in_flight: InFlight::default(),
+        };
+        dispatcher.run(receiver).await;
+    });
+}
+
+struct Dispatcher {
+    http: Arc<serenity::Http>,
+    data: Arc<Data>,
+    lanes: HashMap<LaneKey, LaneSender>,
+    in_flight: InFlight,
+}
+
+impl Dispatcher {
+    async fn run(&mut self, mut receiver: webhook::WebhookReceiver) {
+        match queue::resume(&self.data.database).await {
+            Ok(ids) => {
+                if !ids.is_empty() {
+                    println!("Resuming {} queued webhook payloads", ids.len());
+                }
+                for id in ids {
+                    self.route(id).await;
+                }
+            }
+            Err(e) => eprintln!("Error resuming ingest queue: {}", e),
+        }
+
+        let mut retry_interval = tokio::time::interval(queue::RETRY_INTERVAL);
+
+        loop {
+            tokio::select! {
+                received = receiver.recv() => match received {
+                    Some(id) => self.route(id).await,
+                    None => {
+                        eprintln!("Webhook channel closed, stopping ingest workers");
+                        return;
+                    }
+                },
+                _ = retry_interval.tick() => {
+                    match queue::retryable(&self.data.database).await {
+                        Ok(ids) => {
+                            for id in ids {
+                                self.route(id).await;
+                            }
+                        }
+                        Err(e) => eprintln!("Error reading ingest queue: {}", e),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sends an entry to its team's lane, starting the lane if needed.
+    /// Entries already waiting in a lane are skipped.
+    async fn route(&mut self, id: i64) {
+        if !self.in_flight.start(id) {
+            return;
+        }
+
+        let key = match lane_key(&self.data, id).await {
+            Ok(key) => key,
+            Err(e) => {
+                eprintln!("Error routing queued payload {}: {}", id, e);
+                LaneKey::Unassigned
+            }
+        };
+
+        if let Some(lane) = self.lanes.get(&key) {
+            if lane.send(id).is_ok() {
+                return;
+            }
+        }
+
+        // The lane has stopped, replace it
+        let lane = spawn_lane(
+            key,
+            self.http.clone(),
+            self.data.clone(),
+            self.in_flight.clone(),
+        );
+        if let Err(e) = lane.send(id) {
+            eprintln!("Error sending payload {} to {:?} lane: {}", id, key, e);
+            self.in_flight.finish(id);
+        }
+        self.lanes.insert(key, lane);
+    }
+}
+
+/// Finds the team a queued payload belongs to, by account hash then by name
+async fn lane_key(data: &Data, id: i64) -> Result<LaneKey, Error> {
+    let pool = &data.database;
+
+    let raw = match queue::peek(pool, id).await? {
+        Some(raw) => raw,
+        None => return Ok(LaneKey::Unassigned),
+    };
+    let payload = serde_json::from_str::<WebhookPayload>(&raw)?;
+
+    if let Some((_, team_id, _, _)) =
+        database::get_member_by_account_hash(pool, &payload.dinkAccountHash).await?
+    {
+        return Ok(LaneKey::Team(team_id));
+    }
+
+    let username = payload.playerName.to_lowercase();
+    if let Some((_, team_id, _, _)) = database::get_member_by_username(pool, &username).await? {
+        return Ok(LaneKey::Team(team_id));
+    }
+
+    Ok(LaneKey::Unassigned)
+}
+
+/// Spawns a worker that processes one lane's entries in order.
+/// Each entry runs in its own task, so a panic fails that entry instead of
+/// stopping the lane.
+fn spawn_lane(
+    key: LaneKey,
+    http: Arc<serenity::Http>,
+    data: Arc<Data>,
+    in_flight: InFlight,
+) -> LaneSender {
+    let (sender, mut receiver) = mpsc::unbounded_channel::<i64>();
+
+    tokio::spawn(async move {
+        while let Some(id) = receiver.recv().await {
+            let task = tokio::spawn({
+                let http = http.clone();
+                let data = data.clone();
+                async move { process_queue_entry(&http, &data, id).await }
+            });
+
+            if let Err(e) = task.await {
+                eprintln!("Worker for {:?} panicked on payload {}: {}", key, id, e);
+                let message = format!("worker panicked: {}", e);
+                if let Err(e) =
+                    queue::complete(&data.database, id, IngestStatus::Failed, Some(&message)).await
+                {
+                    eprintln!("Error updating queued payload {}: {}", id, e);
+                }
+            }
+
+            in_flight.finish(id);
+        }
+    });
+
+    sender
+}
+
+/// Claims a queued payload, processes it and records the resulting status
+async fn process_queue_entry(http: &serenity::Http, data: &Data, id: i64) {
+    let pool = &data.database;
+
+    let (raw, received_at) = match queue::claim(pool, id).await {
+        Ok(Some(claimed)) => claimed,
+        Ok(None) => return, // Already handled
+        Err(e) => {
+            eprintln!("Error claiming queued payload {}: {}", id, e);
+            return;
+        }
+    };
+
+    let result = match serde_json::from_str::<WebhookPayload>(&raw) {
+        Ok(mut payload) => {
+            payload.queue_id = Some(id);
+            payload.received_at = received_at;
+            process_webhook(http, data, &payload).await
+        }
+        Err(e) => Err(e.into()),
+    };
+
+    let (status, message) = match result {
+        Ok(outcomes) => {
+            let reasons: Vec<String> = outcomes
+                .iter()
+                .filter_map(|outcome| match outcome {
+                    DropOutcome::Rejected(reason) => Some(reason.code().to_string()),
+                    DropOutcome::Credited => None,
+                })
+                .collect();
+
+            data.metrics.payload_processed();
+
+            if !outcomes.is_empty() && reasons.len() == outcomes.len() {
+                (IngestStatus::Rejected, Some(reasons.join(", ")))
+            } else {
+                (IngestStatus::Processed, None)
+            }
+        }
+        Err(e) => {
+            eprintln!("Error processing webhook {}: {}", id, e);
+            data.metrics.payload_failed();
+            (IngestStatus::Failed, Some(e.to_string()))
+        }
+    };
+
+    if let Err(e) = queue::complete(pool, id, status, message.as_deref()).await {
+        eprintln!("Error updating queued payload {}: {}", id, e);
+    }
+}
+
+async fn process_webhook(
+    http: &serenity::Http,
+    data: &Data,
+    payload: &WebhookPayload,
+) -> Result<Vec<DropOutcome>, Error> {
+    // Deserialize the `extra` object according to the notification type
+    let notification = match dink::notification::DinkNotification::from_payload(payload) {
+        Ok(notification) => notification,
+        Err(e) => {
+            eprintln!(
+                "Failed to read {} notification from {}: {}",
+                payload.r#type, payload.playerName, e
+            );
+            dink::notification::DinkNotification::Other(payload.r#type.clone())
+        }
+    };
+
+    dink::notification::dispatch(http, data, payload, notification).await
+}
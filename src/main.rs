@@ -6,17 +6,16 @@ mod webhook;
 
 use std::{
     collections::HashMap,
-    env::{self, var},
+    env::var,
     sync::Arc,
     time::{Duration, Instant},
 };
 
 use ::serenity::all::GatewayIntents;
 use poise::serenity_prelude as serenity;
-use tokio::sync::Mutex as TokioMutex;
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
-type Context<'a> = poise::Context<'a, Data, Error>;
+type Context<'a> = poise::Context<'a, Arc<Data>, Error>;
 
 pub struct Data {
     dink_channel_id: u64,
@@ -25,11 +24,15 @@ pub struct Data {
     town_config: coc::buildings::TownConfig,
     bestiary: coc::bestiary::Bestiary,
     status_message: tokio::sync::Mutex<Option<(serenity::ChannelId, serenity::MessageId)>>,
-    webhook_receiver: TokioMutex<Option<webhook::WebhookReceiver>>,
     last_embed_update: Arc<tokio::sync::Mutex<HashMap<String, Instant>>>,
+    duplicate_window_secs: i64,
+    world_policy: dink::policy::WorldPolicy,
+    clan_policy: dink::policy::ClanPolicy,
+    metrics: Arc<webhook::metrics::Metrics>,
+    drop_events: dink::events::DropFeed,
 }
 
-async fn on_error(error: poise::FrameworkError<'_, Data, Error>) {
+async fn on_error(error: poise::FrameworkError<'_, Arc<Data>, Error>) {
     match error {
         poise::FrameworkError::Setup { error, .. } => panic!("Failed to start bot: {:?}", error),
         poise::FrameworkError::Command { error, ctx, .. } => {
@@ -46,26 +49,21 @@ async fn on_error(error: poise::FrameworkError<'_, Data, Error>) {
 async fn event_handler(
     ctx: &serenity::Context,
     event: &serenity::FullEvent,
-    _framework: poise::FrameworkContext<'_, Data, Error>,
+    _framework: poise::FrameworkContext<'_, Arc<Data>, Error>,
     data: &Data,
 ) -> Result<(), Error> {
     match event {
         serenity::FullEvent::Ready { data_about_bot, .. } => {
             println!("Logged in as {}", data_about_bot.user.name);
-
-            let ctx_clone = ctx.clone();
-
-            let mut receiver_guard = data.webhook_receiver.lock().await;
-            if let Some(mut receiver) = receiver_guard.take() {
-                drop(receiver_guard);
-
-                while let Some(payload) = receiver.recv().await {
-                    // println!("Processing webhook: {:?}", payload);
-                    if let Err(e) = process_webhook(&ctx_clone, &data, &payload).await {
-                        eprintln!("Error processing webhook: {}", e);
-                    }
-                }
-            }
+            data.metrics.set_gateway_connected(true);
+        }
+        serenity::FullEvent::Resume { .. } => {
+            data.metrics.set_gateway_connected(true);
+        }
+        serenity::FullEvent::ShardStageUpdate { event } => {
+            println!("Shard {} is now {}", event.shard_id, event.new);
+            data.metrics
+                .set_gateway_connected(event.new == serenity::ConnectionStage::Connected);
         }
         serenity::FullEvent::Message { new_message } => {
             if new_message.channel_id.get() == data.dink_channel_id {
@@ -74,106 +72,104 @@ async fn event_handler(
                 }
             }
         }
-        _ => {}
-    }
-    Ok(())
-}
-
-async fn process_webhook(
-    ctx: &serenity::Context,
-    data: &Data,
-    payload: &webhook::WebhookPayload,
-) -> Result<(), Error> {
-    let channel_id = serenity::ChannelId::new(data.dink_channel_id);
-    // println!(
-    //     "Processing webhook from {}: {}",
-    //     payload.playerName, payload.r#type
-    // );
-
-    // Process each embed in the payload
-    for embed in &payload.embeds {
-        let description = &embed.description;
-
-        // println!("Processing embed description: {}", description);
-
-        // Parse the loot text using your existing function
-        match dink::parse_loot_text(description) {
-            Ok(drop) => {
-                println!(
-                    "Processing drop: User: {}, Source: {}, Items: {:?}",
-                    drop.user, drop.source, drop.loots
-                );
-
-                // Process the drop using your existing function
-                if let Err(e) = dink::process_drop(ctx, data, drop).await {
-                    eprintln!("Error processing drop: {}", e);
-                    continue;
-                }
-            }
-            Err(e) => {
-                eprintln!("Failed to parse loot text: {}", e);
-
-                // Send a simple message with the raw embed info if parsing fails
-                let message = format!(
-                    "**New Event from {}**\nType: {}\n\n{}",
-                    payload.playerName, payload.r#type, description
-                );
-
-                if let Err(send_err) = channel_id.say(&ctx.http, message).await {
-                    eprintln!("Error sending message: {}", send_err);
-                }
+        serenity::FullEvent::MessageDelete {
+            channel_id,
+            deleted_message_id,
+            ..
+        } if channel_id.get() == data.dink_channel_id => {
+            if let Err(e) = dink::handle_message_delete(&ctx.http, data, *deleted_message_id).await
+            {
+                println!("Error reverting deleted dink message: {}", e);
             }
         }
+        _ => {}
     }
-
     Ok(())
 }
 
 #[tokio::main]
 async fn main() {
     tracing_subscriber::fmt::init();
+    dotenv::dotenv().expect("Failed to load .env file");
 
     let webhook_port = var("WEBHOOK_PORT")
         .unwrap_or_else(|_| "3000".to_string())
         .parse::<u16>()
         .expect("WEBHOOK_PORT must be a valid port number");
 
-    let (_webhook_sender, webhook_receiver) = webhook::start_webhook_server(webhook_port).await;
+    let webhook_secret = var("WEBHOOK_SECRET")
+        .ok()
+        .map(|secret| secret.trim().to_string())
+        .filter(|secret| !secret.is_empty())
+        .expect("Missing or empty `WEBHOOK_SECRET` env var, see README for more information.");
 
-    let framework = poise::Framework::builder()
-        .setup(move |ctx, _ready, framework| {
-            Box::pin(async move {
-                println!("Logged in as {}", _ready.user.name);
-                poise::builtins::register_globally(ctx, &framework.options().commands).await?;
+    let pool = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(30)
+        .min_connections(10)
+        .connect(&var("DATABASE_URL").expect("Missing `DATABASE_URL` env var"))
+        .await
+        .expect("Failed to connect to database");
 
-                let dink_channel_id = var("DINK_UPDATES_CHANNEL_ID")
-                    .expect("Missing `DINK_UPDATES_CHANNEL_ID` env var")
-                    .parse::<u64>()
-                    .expect("DINK_UPDATES_CHANNEL_ID must be a valid u64");
+    let admin_token = var("ADMIN_API_TOKEN")
+        .ok()
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty());
 
-                let pool_options = sqlx::sqlite::SqlitePoolOptions::new()
-                    .max_connections(30)
-                    .min_connections(10);
+    let metrics = Arc::new(webhook::metrics::Metrics::default());
 
-                let pool = pool_options.connect(&env::var("DATABASE_URL")?).await?;
+    let dink_channel_id = var("DINK_UPDATES_CHANNEL_ID")
+        .expect("Missing `DINK_UPDATES_CHANNEL_ID` env var")
+        .parse::<u64>()
+        .expect("DINK_UPDATES_CHANNEL_ID must be a valid u64");
 
-                let res_patterns = coc::patterns::load_res_patterns();
+    let res_patterns = coc::patterns::load_res_patterns();
 
-                let town_config =
-                    coc::buildings::init_assets().expect("could not load town config");
+    let town_config = coc::buildings::init_assets().expect("could not load town config");
+    let categories: Vec<&str> = res_patterns
+        .resource_pattern
+        .iter()
+        .map(|pattern| pattern.category.as_str())
+        .collect();
+    town_config
+        .validate_source_categories(&categories)
+        .expect("invalid source access config");
 
-                let bestiary = coc::bestiary::init_bestiary().expect("could not load bestiary");
+    let bestiary = coc::bestiary::init_bestiary().expect("could not load bestiary");
 
-                Ok(Data {
-                    dink_channel_id,
-                    database: pool,
-                    res_patterns,
-                    town_config,
-                    bestiary,
-                    status_message: tokio::sync::Mutex::new(None),
-                    webhook_receiver: TokioMutex::new(Some(webhook_receiver)),
-                    last_embed_update: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
-                })
+    let world_policy = dink::policy::init_world_policy().expect("could not load world policy");
+
+    let clan_policy = dink::policy::init_clan_policy().expect("could not load clan policy");
+
+    let duplicate_window_secs = var("DUPLICATE_WINDOW_SECS")
+        .unwrap_or_else(|_| "10".to_string())
+        .parse::<u32>()
+        .map(i64::from)
+        .expect("DUPLICATE_WINDOW_SECS must be a non-negative number of seconds");
+
+    // Shared between commands, events and the ingest workers
+    let data = Arc::new(Data {
+        dink_channel_id,
+        database: pool,
+        res_patterns,
+        town_config,
+        bestiary,
+        status_message: tokio::sync::Mutex::new(None),
+        last_embed_update: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
+        duplicate_window_secs,
+        world_policy,
+        clan_policy,
+        metrics,
+        drop_events: dink::events::DropFeed::new(),
+    });
+
+    let framework_data = data.clone();
+    let framework = poise::Framework::builder()
+        .setup(move |ctx, _ready, framework| {
+            Box::pin(async move {
+                println!("Logged in as {}", _ready.user.name);
+                poise::builtins::register_globally(ctx, &framework.options().commands).await?;
+
+                Ok(framework_data)
             })
         })
         .options(poise::FrameworkOptions {
@@ -184,6 +180,10 @@ async fn main() {
                 coc::commands::add_team(),
                 coc::commands::remove_team(),
                 coc::commands::remove_player(),
+                coc::commands::confirm_account(),
+                coc::commands::unbind_account(),
+                coc::commands::add_clan_guest(),
+                coc::commands::remove_clan_guest(),
                 coc::commands::create_resource_embed(),
                 coc::commands::list_team_resources(),
                 coc::commands::upgrade_building(),
@@ -194,6 +194,14 @@ async fn main() {
                 coc::commands::buildings_overview(),
                 coc::commands::force_upgrade_building(),
                 coc::commands::force_insert_resource(),
+                coc::commands::list_duplicate_drops(),
+                coc::commands::list_rejected_drops(),
+                coc::commands::revert_drop(),
+                coc::commands::set_event_window(),
+                coc::commands::pause_event(),
+                coc::commands::resume_event(),
+                coc::commands::explain_drop(),
+                coc::commands::explain_drop_message(),
                 commands::simple_embed(),
                 commands::edit_embed(),
             ],
@@ -241,7 +249,6 @@ async fn main() {
         })
         .build();
 
-    dotenv::dotenv().expect("Failed to load .env file");
     let token = var("DISCORD_TOKEN")
         .expect("Missing `DISCORD_TOKEN` env var, see README for more information.");
     let intents = serenity::GatewayIntents::non_privileged()
@@ -249,9 +256,22 @@ async fn main() {
         | GatewayIntents::GUILD_MESSAGES
         | GatewayIntents::DIRECT_MESSAGES;
 
-    let client = serenity::ClientBuilder::new(token, intents)
+    let mut client = serenity::ClientBuilder::new(token, intents)
         .framework(framework)
-        .await;
+        .await
+        .unwrap();
+
+    let (_webhook_sender, webhook_receiver) = webhook::start_webhook_server(
+        webhook_port,
+        webhook_secret,
+        admin_token,
+        data.clone(),
+        client.http.clone(),
+    )
+    .await;
+
+    // Drops are processed independently of the gateway connection
+    dink::worker::start(client.http.clone(), data, webhook_receiver);
 
-    client.unwrap().start().await.unwrap()
+    client.start().await.unwrap()
 }
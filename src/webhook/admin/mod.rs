@@ -0,0 +1,244 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, Path, Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, post},
+    Extension, Json, Router,
+};
+use serde::Deserialize;
+
+use super::{constant_time_eq, header_token, AppState, WebhookResponse};
+use crate::coc::admin::{self, Actor, Outcome, Refusal};
+use crate::coc::commands::{update_global_embeds, update_team_embeds};
+use crate::Error;
+
+#[derive(Deserialize)]
+pub struct NewTeam {
+    pub name: String,
+    pub handicap: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct NewPlayer {
+    pub username: String,
+}
+
+#[derive(Deserialize)]
+pub struct NewResource {
+    pub resource: String,
+    pub quantity: i64,
+}
+
+/// Admin routes mirroring the owner-only commands, authenticated with
+/// `Authorization: Bearer <ADMIN_API_TOKEN>`
+pub fn router(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/api/admin/teams", post(add_team))
+        .route("/api/admin/teams/{name}", delete(remove_team))
+        .route("/api/admin/teams/{name}/players", post(add_player))
+        .route("/api/admin/players/{username}", delete(remove_player))
+        .route("/api/admin/teams/{name}/resources", post(insert_resource))
+        .route(
+            "/api/admin/teams/{name}/buildings/{building}/upgrade",
+            post(upgrade_building),
+        )
+        .route(
+            "/api/admin/teams/{name}/buildings/{building}/downgrade",
+            post(downgrade_building),
+        )
+        // Authorize before any extractor runs, so callers without a token
+        // can't learn the request schema from extraction errors
+        .route_layer(middleware::from_fn_with_state(state, require_admin))
+}
+
+fn message_response(code: StatusCode, status: &str, message: String) -> Response {
+    (
+        code,
+        Json(WebhookResponse {
+            status: status.to_string(),
+            message,
+        }),
+    )
+        .into_response()
+}
+
+/// Rejects unauthorized admin requests, and hands handlers the actor to
+/// audit the call under
+async fn require_admin(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    match authorize(&state, addr, request.headers()) {
+        Ok(actor) => {
+            request.extensions_mut().insert(actor);
+            next.run(request).await
+        }
+        Err(rejection) => rejection.into_response(),
+    }
+}
+
+/// Checks the caller's token and returns the actor to audit the call under
+fn authorize(
+    state: &AppState,
+    addr: SocketAddr,
+    headers: &HeaderMap,
+) -> Result<Actor, (StatusCode, Json<WebhookResponse>)> {
+    let expected = match &state.admin_token {
+        Some(token) => token,
+        None => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(WebhookResponse {
+                    status: "error".to_string(),
+                    message: "Admin API is disabled".to_string(),
+                }),
+            ))
+        }
+    };
+
+    match header_token(headers) {
+        Some(token) if constant_time_eq(token.as_bytes(), expected.as_bytes()) => {
+            Ok(Actor::api(&addr.ip().to_string()))
+        }
+        _ => {
+            let total = state.data.metrics.auth_rejected();
+            eprintln!(
+                "[WARN] Rejected admin request from {} (total rejected: {})",
+                addr, total
+            );
+            Err((
+                StatusCode::UNAUTHORIZED,
+                Json(WebhookResponse {
+                    status: "error".to_string(),
+                    message: "Unauthorized".to_string(),
+                }),
+            ))
+        }
+    }
+}
+
+// Turns the result of an admin action into a JSON response
+fn respond(result: Result<Outcome, Error>) -> Response {
+    match result {
+        Ok(Ok(message)) => message_response(StatusCode::OK, "success", message),
+        Ok(Err(refusal)) => {
+            let code = match refusal {
+                Refusal::NotFound(_) => StatusCode::NOT_FOUND,
+                Refusal::Conflict(_) => StatusCode::CONFLICT,
+                Refusal::Invalid(_) => StatusCode::BAD_REQUEST,
+            };
+            message_response(code, "error", refusal.to_string())
+        }
+        Err(e) => {
+            eprintln!("[ERROR] Admin request failed: {}", e);
+            message_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "error",
+                "Failed to run admin action".to_string(),
+            )
+        }
+    }
+}
+
+/// Refreshes a team's embeds after a change, and the town hall ranking when
+/// the town hall changed. Failures are only logged since the change is done.
+async fn refresh_embeds(state: &AppState, team_name: &str, building_name: Option<&str>) {
+    let team_name = team_name.to_lowercase();
+    if let Err(e) = update_team_embeds(&state.http, &state.data, &team_name).await {
+        eprintln!("Error updating team embeds for '{}': {}", team_name, e);
+    }
+
+    let building_name = building_name.map(str::to_lowercase);
+    if matches!(building_name.as_deref(), Some("townhall" | "town_hall")) {
+        if let Err(e) =
+            update_global_embeds(&state.http, &state.data, Some("townhall_ranking")).await
+        {
+            eprintln!("Error updating global embeds: {}", e);
+        }
+    }
+}
+
+async fn add_team(
+    State(state): State<AppState>,
+    Extension(actor): Extension<Actor>,
+    Json(team): Json<NewTeam>,
+) -> Response {
+    respond(admin::add_team(&state.data, &actor, &team.name, team.handicap).await)
+}
+
+async fn remove_team(
+    State(state): State<AppState>,
+    Extension(actor): Extension<Actor>,
+    Path(name): Path<String>,
+) -> Response {
+    respond(admin::remove_team(&state.data, &actor, &name).await)
+}
+
+async fn add_player(
+    State(state): State<AppState>,
+    Extension(actor): Extension<Actor>,
+    Path(name): Path<String>,
+    Json(player): Json<NewPlayer>,
+) -> Response {
+    respond(admin::add_player(&state.data, &actor, &player.username, &name).await)
+}
+
+async fn remove_player(
+    State(state): State<AppState>,
+    Extension(actor): Extension<Actor>,
+    Path(username): Path<String>,
+) -> Response {
+    respond(admin::remove_player(&state.data, &actor, &username).await)
+}
+
+async fn insert_resource(
+    State(state): State<AppState>,
+    Extension(actor): Extension<Actor>,
+    Path(name): Path<String>,
+    Json(resource): Json<NewResource>,
+) -> Response {
+    let result = admin::force_insert_resource(
+        &state.data,
+        &actor,
+        &name,
+        &resource.resource,
+        resource.quantity,
+    )
+    .await;
+    if matches!(result, Ok(Ok(_))) {
+        refresh_embeds(&state, &name, None).await;
+    }
+
+    respond(result)
+}
+
+async fn upgrade_building(
+    State(state): State<AppState>,
+    Extension(actor): Extension<Actor>,
+    Path((name, building)): Path<(String, String)>,
+) -> Response {
+    let result = admin::force_upgrade_building(&state.data, &actor, &name, &building).await;
+    if matches!(result, Ok(Ok(_))) {
+        refresh_embeds(&state, &name, Some(&building)).await;
+    }
+
+    respond(result)
+}
+
+async fn downgrade_building(
+    State(state): State<AppState>,
+    Extension(actor): Extension<Actor>,
+    Path((name, building)): Path<(String, String)>,
+) -> Response {
+    let result = admin::downgrade_building(&state.data, &actor, &name, &building).await;
+    if matches!(result, Ok(Ok(_))) {
+        refresh_embeds(&state, &name, Some(&building)).await;
+    }
+
+    respond(result)
+}
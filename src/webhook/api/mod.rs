@@ -0,0 +1,236 @@
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::get,
+    Json, Router,
+};
+use serde::Serialize;
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+
+use super::{AppState, WebhookResponse};
+use crate::coc::database;
+use crate::Error;
+
+#[derive(Serialize)]
+pub struct TeamSummary {
+    pub id: i32,
+    pub name: String,
+    pub members: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct ResourceItem {
+    pub name: String,
+    pub category: String,
+    pub quantity: i64,
+}
+
+#[derive(Serialize)]
+pub struct TeamResources {
+    pub team: String,
+    pub items: Vec<ResourceItem>,
+    pub categories: BTreeMap<String, i64>,
+}
+
+#[derive(Serialize)]
+pub struct Building {
+    pub name: String,
+    pub level: i32,
+}
+
+#[derive(Serialize)]
+pub struct Multiplier {
+    pub building: String,
+    pub category: String,
+    pub multiplier: f64,
+    pub flat_bonus: i64,
+}
+
+#[derive(Serialize)]
+pub struct TeamBuildings {
+    pub team: String,
+    pub buildings: Vec<Building>,
+    pub multipliers: Vec<Multiplier>,
+}
+
+#[derive(Serialize)]
+pub struct LeaderboardEntry {
+    pub rank: usize,
+    pub team: String,
+    pub town_hall_level: i32,
+    pub total_resources: i64,
+}
+
+/// Read-only routes exposing event state as JSON
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/teams", get(list_teams))
+        .route("/api/teams/{name}/resources", get(team_resources))
+        .route("/api/teams/{name}/buildings", get(team_buildings))
+        .route("/api/leaderboard", get(leaderboard))
+        .route("/api/drops/stream", get(drop_stream))
+}
+
+fn error_response(code: StatusCode, message: String) -> Response {
+    (
+        code,
+        Json(WebhookResponse {
+            status: "error".to_string(),
+            message,
+        }),
+    )
+        .into_response()
+}
+
+// Turns a handler result into a JSON response, hiding internal errors
+fn respond<T: Serialize>(result: Result<Option<T>, Error>, team_name: &str) -> Response {
+    match result {
+        Ok(Some(body)) => Json(body).into_response(),
+        Ok(None) => error_response(
+            StatusCode::NOT_FOUND,
+            format!("No team found with name '{}'", team_name),
+        ),
+        Err(e) => {
+            eprintln!("[ERROR] API request failed: {}", e);
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to read event data".to_string(),
+            )
+        }
+    }
+}
+
+async fn list_teams(State(state): State<AppState>) -> Response {
+    let result: Result<Option<Vec<TeamSummary>>, Error> = async {
+        let mut teams = Vec::new();
+        for (id, name) in database::get_all_teams(&state.data.database).await? {
+            let members = database::get_team_members(&state.data.database, id).await?;
+            teams.push(TeamSummary { id, name, members });
+        }
+        Ok(Some(teams))
+    }
+    .await;
+
+    respond(result, "")
+}
+
+async fn team_resources(State(state): State<AppState>, Path(name): Path<String>) -> Response {
+    let team_name = name.to_lowercase();
+    let result = async {
+        let team_id = match database::get_team_by_name(&state.data.database, &team_name).await? {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+
+        let mut categories = BTreeMap::new();
+        let items = database::get_team_resource_items(&state.data.database, team_id)
+            .await?
+            .into_iter()
+            .map(|(name, category, quantity)| {
+                *categories.entry(category.clone()).or_insert(0) += quantity;
+                ResourceItem {
+                    name,
+                    category,
+                    quantity,
+                }
+            })
+            .collect();
+
+        Ok(Some(TeamResources {
+            team: team_name.clone(),
+            items,
+            categories,
+        }))
+    }
+    .await;
+
+    respond(result, &team_name)
+}
+
+async fn team_buildings(State(state): State<AppState>, Path(name): Path<String>) -> Response {
+    let team_name = name.to_lowercase();
+    let result = async {
+        let team_id = match database::get_team_by_name(&state.data.database, &team_name).await? {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+
+        let buildings = database::get_team_buildings(&state.data.database, team_id)
+            .await?
+            .into_iter()
+            .map(|(name, level)| Building { name, level })
+            .collect();
+
+        let multipliers = database::get_team_multipliers(&state.data.database, team_id)
+            .await?
+            .into_iter()
+            .map(|(building, category, multiplier, flat_bonus)| Multiplier {
+                building,
+                category,
+                multiplier,
+                flat_bonus,
+            })
+            .collect();
+
+        Ok(Some(TeamBuildings {
+            team: team_name.clone(),
+            buildings,
+            multipliers,
+        }))
+    }
+    .await;
+
+    respond(result, &team_name)
+}
+
+async fn leaderboard(State(state): State<AppState>) -> Response {
+    let result = async {
+        let entries = database::get_leaderboard(&state.data.database)
+            .await?
+            .into_iter()
+            .enumerate()
+            .map(
+                |(index, (team, town_hall_level, total_resources))| LeaderboardEntry {
+                    rank: index + 1,
+                    team,
+                    town_hall_level,
+                    total_resources,
+                },
+            )
+            .collect::<Vec<_>>();
+
+        Ok(Some(entries))
+    }
+    .await;
+
+    respond(result, "")
+}
+
+/// Streams one `drop` event per processed drop as Server-Sent Events.
+/// Subscribers that fall too far behind skip the events they missed.
+async fn drop_stream(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let receiver = state.data.drop_events.subscribe();
+
+    let stream = BroadcastStream::new(receiver).filter_map(|event| {
+        let event = event.ok()?;
+        match Event::default().event("drop").json_data(&event) {
+            Ok(sse_event) => Some(Ok(sse_event)),
+            Err(e) => {
+                eprintln!("[ERROR] Failed to serialize drop event: {}", e);
+                None
+            }
+        }
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::new().interval(Duration::from_secs(15)))
+}
@@ -0,0 +1,268 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+
+use crate::dink::DropOutcome;
+
+/// Counters exposed on `/metrics` in the Prometheus text format
+#[derive(Default)]
+pub struct Metrics {
+    payloads_received: AtomicU64,
+    payloads_processed: AtomicU64,
+    payloads_failed: AtomicU64,
+    last_processed_at: AtomicI64,
+    gateway_connected: AtomicBool,
+    auth_rejections: AtomicU64,
+    last_payload_at: AtomicI64,
+    drops_credited: AtomicU64,
+    drops_rejected: Mutex<BTreeMap<&'static str, u64>>,
+    items_credited: Mutex<BTreeMap<String, u64>>,
+    embed_edits: AtomicU64,
+    discord_errors: AtomicU64,
+}
+
+/// Queue sizes read when the metrics are scraped
+pub struct QueueDepth {
+    pub channel: usize,
+    pub backlog: Option<i64>,
+}
+
+impl Metrics {
+    /// Counts a payload accepted by the webhook server
+    pub fn payload_received(&self) {
+        self.payloads_received.fetch_add(1, Ordering::Relaxed);
+        self.last_payload_at
+            .store(Utc::now().timestamp(), Ordering::Relaxed);
+    }
+
+    /// Counts a queued payload that finished processing
+    pub fn payload_processed(&self) {
+        self.payloads_processed.fetch_add(1, Ordering::Relaxed);
+        self.last_processed_at
+            .store(Utc::now().timestamp(), Ordering::Relaxed);
+    }
+
+    /// When a payload last finished processing, if any has since startup
+    pub fn last_processed_at(&self) -> Option<DateTime<Utc>> {
+        match self.last_processed_at.load(Ordering::Relaxed) {
+            0 => None,
+            timestamp => DateTime::from_timestamp(timestamp, 0),
+        }
+    }
+
+    /// Records whether the Discord gateway connection is up
+    pub fn set_gateway_connected(&self, connected: bool) {
+        self.gateway_connected.store(connected, Ordering::Relaxed);
+    }
+
+    pub fn gateway_connected(&self) -> bool {
+        self.gateway_connected.load(Ordering::Relaxed)
+    }
+
+    /// Counts a processing attempt that failed and may be retried
+    pub fn payload_failed(&self) {
+        self.payloads_failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a request turned away for a missing or wrong secret, returning the new total
+    pub fn auth_rejected(&self) -> u64 {
+        self.auth_rejections.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Counts a drop by its outcome, and rejected drops by reason
+    pub fn drop_outcome(&self, outcome: &DropOutcome) {
+        match outcome {
+            DropOutcome::Credited => {
+                self.drops_credited.fetch_add(1, Ordering::Relaxed);
+            }
+            DropOutcome::Rejected(reason) => {
+                let mut rejected = self.drops_rejected.lock().unwrap();
+                *rejected.entry(reason.code()).or_insert(0) += 1;
+            }
+        }
+    }
+
+    /// Adds credited items to their category's total
+    pub fn items_credited(&self, category: &str, quantity: u64) {
+        let mut items = self.items_credited.lock().unwrap();
+        *items.entry(category.to_string()).or_insert(0) += quantity;
+    }
+
+    /// Counts a successful embed edit
+    pub fn embed_edited(&self) {
+        self.embed_edits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a failed Discord API call
+    pub fn discord_error(&self) {
+        self.discord_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Renders every metric in the Prometheus text exposition format
+    pub fn render(&self, queue: &QueueDepth) -> String {
+        let mut out = String::new();
+
+        counter(
+            &mut out,
+            "dink_payloads_received_total",
+            "Webhook payloads accepted",
+            self.payloads_received.load(Ordering::Relaxed),
+        );
+        counter(
+            &mut out,
+            "dink_payloads_processed_total",
+            "Webhook payloads processed",
+            self.payloads_processed.load(Ordering::Relaxed),
+        );
+        counter(
+            &mut out,
+            "dink_payloads_failed_total",
+            "Webhook payload processing attempts that failed",
+            self.payloads_failed.load(Ordering::Relaxed),
+        );
+        gauge(
+            &mut out,
+            "dink_last_processed_timestamp_seconds",
+            "Unix time the last payload finished processing",
+            self.last_processed_at.load(Ordering::Relaxed),
+        );
+        counter(
+            &mut out,
+            "dink_auth_rejections_total",
+            "Webhook requests rejected for a missing or invalid secret",
+            self.auth_rejections.load(Ordering::Relaxed),
+        );
+        gauge(
+            &mut out,
+            "dink_last_payload_timestamp_seconds",
+            "Unix time of the last accepted payload",
+            self.last_payload_at.load(Ordering::Relaxed),
+        );
+        counter(
+            &mut out,
+            "dink_drops_credited_total",
+            "Drops credited to a team",
+            self.drops_credited.load(Ordering::Relaxed),
+        );
+
+        header(
+            &mut out,
+            "dink_drops_rejected_total",
+            "Drops rejected, by reason",
+            "counter",
+        );
+        for (reason, count) in self.drops_rejected.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "dink_drops_rejected_total{{reason=\"{}\"}} {}",
+                reason, count
+            );
+        }
+
+        header(
+            &mut out,
+            "dink_items_credited_total",
+            "Items credited to teams, by category",
+            "counter",
+        );
+        for (category, count) in self.items_credited.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "dink_items_credited_total{{category=\"{}\"}} {}",
+                escape_label(category),
+                count
+            );
+        }
+
+        counter(
+            &mut out,
+            "discord_embed_edits_total",
+            "Team and global embeds edited",
+            self.embed_edits.load(Ordering::Relaxed),
+        );
+        counter(
+            &mut out,
+            "discord_errors_total",
+            "Failed Discord API calls",
+            self.discord_errors.load(Ordering::Relaxed),
+        );
+        gauge(
+            &mut out,
+            "discord_gateway_connected",
+            "Whether the Discord gateway connection is up",
+            self.gateway_connected() as i64,
+        );
+
+        gauge(
+            &mut out,
+            "dink_queue_channel_depth",
+            "Payloads waiting in the in-memory channel",
+            queue.channel as i64,
+        );
+        if let Some(backlog) = queue.backlog {
+            gauge(
+                &mut out,
+                "dink_queue_backlog",
+                "Stored payloads waiting for or in processing",
+                backlog,
+            );
+        }
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: u64) {
+    header(out, name, help, "counter");
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: i64) {
+    header(out, name, help, "gauge");
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+/// Escapes a label value as required by the text format
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dink::RejectReason;
+
+    #[test]
+    fn test_render_metrics() {
+        let metrics = Metrics::default();
+        metrics.payload_received();
+        metrics.drop_outcome(&DropOutcome::Credited);
+        metrics.drop_outcome(&DropOutcome::Rejected(RejectReason::NoTeam));
+        metrics.drop_outcome(&DropOutcome::Rejected(RejectReason::NoTeam));
+        metrics.items_credited("bones", 3);
+
+        let text = metrics.render(&QueueDepth {
+            channel: 2,
+            backlog: Some(5),
+        });
+
+        assert!(text.contains("# TYPE dink_payloads_received_total counter\n"));
+        assert!(text.contains("dink_payloads_received_total 1\n"));
+        assert!(text.contains("dink_drops_credited_total 1\n"));
+        assert!(text.contains("dink_drops_rejected_total{reason=\"no_team\"} 2\n"));
+        assert!(text.contains("dink_items_credited_total{category=\"bones\"} 3\n"));
+        assert!(text.contains("dink_queue_channel_depth 2\n"));
+        assert!(text.contains("dink_queue_backlog 5\n"));
+    }
+}
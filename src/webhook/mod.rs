@@ -1,12 +1,27 @@
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
 use axum::{
-    extract::{Multipart, State},
-    http::StatusCode,
+    extract::{ConnectInfo, Multipart, Path, State},
+    http::{
+        header::{AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER},
+        HeaderMap, StatusCode,
+    },
+    response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
+use poise::serenity_prelude as serenity;
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc;
 
+use crate::Data;
+
+pub mod admin;
+pub mod api;
+pub mod metrics;
+pub mod pages;
+pub mod queue;
+
 // Update the webhook payload structure to match Discord's format
 #[derive(Debug, Deserialize, Serialize)]
 pub struct WebhookPayload {
@@ -20,6 +35,12 @@ pub struct WebhookPayload {
     pub regionId: i32,
     pub extra: serde_json::Value, // Use generic Value for complex nested structures
     pub embeds: Vec<Embed>,
+    /// The ingest queue row the payload was read from
+    #[serde(skip)]
+    pub queue_id: Option<i64>,
+    /// Unix time the payload reached the webhook server
+    #[serde(skip)]
+    pub received_at: Option<i64>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -66,21 +87,129 @@ pub struct WebhookResponse {
     pub message: String,
 }
 
-// Channel for communicating with the main bot
-pub type WebhookSender = mpsc::Sender<WebhookPayload>;
-pub type WebhookReceiver = mpsc::Receiver<WebhookPayload>;
+// Health response, covering each dependency of the bot
+#[derive(Serialize)]
+pub struct HealthResponse {
+    pub status: String,
+    pub message: String,
+    pub database: bool,
+    pub gateway_connected: bool,
+    pub channel_depth: usize,
+    pub channel_capacity: usize,
+    pub queue_backlog: Option<i64>,
+    pub last_processed_at: Option<String>,
+}
+
+// Channel for communicating with the main bot, carrying `ingest_queue` IDs
+pub type WebhookSender = mpsc::Sender<i64>;
+pub type WebhookReceiver = mpsc::Receiver<i64>;
+
+// Number of payloads the channel holds before requests are turned away
+const CHANNEL_CAPACITY: usize = 100;
+
+// How long a request waits for room in the channel before giving up
+const ENQUEUE_TIMEOUT: Duration = Duration::from_millis(250);
+
+// Seconds a client is asked to wait before retrying a saturated request
+const RETRY_AFTER_SECS: u64 = 5;
+
+// Header checked for the shared secret when it isn't passed in the URL path
+const TOKEN_HEADER: &str = "x-webhook-token";
 
 // AppState to share data between routes
 #[derive(Clone)]
 pub struct AppState {
+    data: Arc<Data>,
+    http: Arc<serenity::Http>,
     webhook_sender: WebhookSender,
+    webhook_secret: Arc<String>,
+    admin_token: Option<Arc<String>>,
 }
 
-// Handler for webhook POST requests
+/// Compares two byte strings without short-circuiting on the first mismatch
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Extracts the secret from either `Authorization: Bearer <token>` or `X-Webhook-Token`
+fn header_token(headers: &HeaderMap) -> Option<&str> {
+    if let Some(value) = headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok()) {
+        if let Some(token) = value.strip_prefix("Bearer ") {
+            return Some(token.trim());
+        }
+    }
+
+    headers
+        .get(TOKEN_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+}
+
+/// Checks a request token against the configured secret.
+/// Failed attempts are counted and logged with the caller's address.
+fn authorize(
+    state: &AppState,
+    addr: SocketAddr,
+    token: Option<&str>,
+) -> Result<(), (StatusCode, Json<WebhookResponse>)> {
+    let reason = match token {
+        Some("") | None => "missing token",
+        Some(token) if constant_time_eq(token.as_bytes(), state.webhook_secret.as_bytes()) => {
+            return Ok(());
+        }
+        Some(_) => "invalid token",
+    };
+
+    let total = state.data.metrics.auth_rejected();
+    eprintln!(
+        "[WARN] Rejected webhook from {}: {} (total rejected: {})",
+        addr, reason, total
+    );
+
+    Err((
+        StatusCode::UNAUTHORIZED,
+        Json(WebhookResponse {
+            status: "error".to_string(),
+            message: "Unauthorized".to_string(),
+        }),
+    ))
+}
+
+// Handler for webhook POST requests authenticated by header
 async fn handle_webhook(
     State(state): State<AppState>,
-    mut multipart: Multipart,
-) -> (StatusCode, Json<WebhookResponse>) {
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    multipart: Multipart,
+) -> Response {
+    if let Err(rejection) = authorize(&state, addr, header_token(&headers)) {
+        return rejection.into_response();
+    }
+
+    receive_payload(state, multipart).await
+}
+
+// Handler for webhook POST requests authenticated by URL path, for clients
+// such as Dink that can't set custom headers
+async fn handle_webhook_with_token(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(token): Path<String>,
+    multipart: Multipart,
+) -> Response {
+    if let Err(rejection) = authorize(&state, addr, Some(&token)) {
+        return rejection.into_response();
+    }
+
+    receive_payload(state, multipart).await
+}
+
+// Reads `payload_json` from an authenticated request and forwards it to the bot
+async fn receive_payload(state: AppState, mut multipart: Multipart) -> Response {
     let mut payload_json = None;
 
     // Debug: Log before processing multipart form
@@ -93,8 +222,8 @@ async fn handle_webhook(
                     Ok(value) => {
                         // Parse the JSON string into your WebhookPayload struct
                         match serde_json::from_str::<WebhookPayload>(&value) {
-                            Ok(payload) => {
-                                payload_json = Some(payload);
+                            Ok(_) => {
+                                payload_json = Some(value);
                             }
                             Err(e) => {
                                 eprintln!("[ERROR] Failed to parse payload_json: {}", e);
@@ -104,7 +233,8 @@ async fn handle_webhook(
                                         status: "error".to_string(),
                                         message: "Invalid payload format".to_string(),
                                     }),
-                                );
+                                )
+                                    .into_response();
                             }
                         }
                     }
@@ -119,20 +249,51 @@ async fn handle_webhook(
 
     // Process the payload
     if let Some(payload) = payload_json {
-        // Send the webhook payload to the main bot process
-        match state.webhook_sender.send(payload).await {
-            Ok(_) => {}
+        // Wait briefly for room in the channel rather than piling up requests
+        // while the bot is busy, and tell the client to back off if it's full
+        let permit =
+            match tokio::time::timeout(ENQUEUE_TIMEOUT, state.webhook_sender.reserve()).await {
+                Ok(Ok(permit)) => Some(permit),
+                Ok(Err(e)) => {
+                    // The entry is still picked up from the queue on the next startup
+                    eprintln!("[ERROR] Webhook channel closed: {}", e);
+                    None
+                }
+                Err(_) => {
+                    eprintln!("[WARN] Webhook channel full, asking client to retry");
+                    return (
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        [(RETRY_AFTER, RETRY_AFTER_SECS.to_string())],
+                        Json(WebhookResponse {
+                            status: "error".to_string(),
+                            message: "Webhook queue is full".to_string(),
+                        }),
+                    )
+                        .into_response();
+                }
+            };
+
+        // Store the payload before acknowledging it so it survives a restart
+        let queue_id = match queue::enqueue(&state.data.database, &payload).await {
+            Ok(id) => id,
             Err(e) => {
-                eprintln!("[ERROR] Failed to send webhook payload: {}", e);
+                eprintln!("[ERROR] Failed to queue webhook payload: {}", e);
                 return (
                     StatusCode::INTERNAL_SERVER_ERROR,
                     Json(WebhookResponse {
                         status: "error".to_string(),
                         message: "Failed to process webhook".to_string(),
                     }),
-                );
+                )
+                    .into_response();
             }
+        };
+
+        // Notify the main bot process
+        if let Some(permit) = permit {
+            permit.send(queue_id);
         }
+        state.data.metrics.payload_received();
 
         return (
             StatusCode::OK,
@@ -140,7 +301,8 @@ async fn handle_webhook(
                 status: "success".to_string(),
                 message: "Webhook received".to_string(),
             }),
-        );
+        )
+            .into_response();
     }
 
     (
@@ -150,43 +312,136 @@ async fn handle_webhook(
             message: "Missing payload_json field".to_string(),
         }),
     )
+        .into_response()
+}
+
+// Reads how many payloads are waiting in the channel and the stored queue
+async fn queue_depth(state: &AppState) -> metrics::QueueDepth {
+    let sender = &state.webhook_sender;
+    let backlog = match queue::backlog(&state.data.database).await {
+        Ok(count) => Some(count),
+        Err(e) => {
+            eprintln!("[ERROR] Failed to count queued payloads: {}", e);
+            None
+        }
+    };
+
+    metrics::QueueDepth {
+        channel: sender.max_capacity() - sender.capacity(),
+        backlog,
+    }
 }
 
-// Health check endpoint
-async fn health_check() -> (StatusCode, Json<WebhookResponse>) {
+// Health check endpoint. Returns 503 when the database doesn't answer, the
+// Discord gateway is down or the channel is full, so a supervisor can restart
+// the bot.
+async fn health_check(State(state): State<AppState>) -> (StatusCode, Json<HealthResponse>) {
+    let database = match crate::coc::database::ping(&state.data.database).await {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("[ERROR] Database health check failed: {}", e);
+            false
+        }
+    };
+    let gateway_connected = state.data.metrics.gateway_connected();
+    let depth = queue_depth(&state).await;
+    let channel_capacity = state.webhook_sender.max_capacity();
+
+    let mut problems = Vec::new();
+    if !database {
+        problems.push("database unavailable");
+    }
+    if !gateway_connected {
+        problems.push("discord gateway disconnected");
+    }
+    if depth.channel >= channel_capacity {
+        problems.push("webhook queue full");
+    }
+
+    let (code, status, message) = if problems.is_empty() {
+        (
+            StatusCode::OK,
+            "success",
+            "Webhook server is running".to_string(),
+        )
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "degraded",
+            problems.join(", "),
+        )
+    };
+
     (
-        StatusCode::OK,
-        Json(WebhookResponse {
-            status: "success".to_string(),
-            message: "Webhook server is running".to_string(),
+        code,
+        Json(HealthResponse {
+            status: status.to_string(),
+            message,
+            database,
+            gateway_connected,
+            channel_depth: depth.channel,
+            channel_capacity,
+            queue_backlog: depth.backlog,
+            last_processed_at: state
+                .data
+                .metrics
+                .last_processed_at()
+                .map(|time| time.to_rfc3339()),
         }),
     )
 }
 
+// Prometheus metrics endpoint
+async fn metrics_handler(State(state): State<AppState>) -> Response {
+    let depth = queue_depth(&state).await;
+
+    (
+        [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.data.metrics.render(&depth),
+    )
+        .into_response()
+}
+
 // Start the webhook server
-pub async fn start_webhook_server(port: u16) -> (WebhookSender, WebhookReceiver) {
+pub async fn start_webhook_server(
+    port: u16,
+    webhook_secret: String,
+    admin_token: Option<String>,
+    data: Arc<Data>,
+    http: Arc<serenity::Http>,
+) -> (WebhookSender, WebhookReceiver) {
     // Create a channel for communication
-    let (webhook_sender, webhook_receiver) = mpsc::channel(100);
+    let (webhook_sender, webhook_receiver) = mpsc::channel(CHANNEL_CAPACITY);
 
     // Create app state
     let state = AppState {
+        data,
+        http,
         webhook_sender: webhook_sender.clone(),
+        webhook_secret: Arc::new(webhook_secret),
+        admin_token: admin_token.map(Arc::new),
     };
 
     // Build the router
     let app = Router::new()
         .route("/webhook", post(handle_webhook))
+        .route("/webhook/{token}", post(handle_webhook_with_token))
         .route("/health", get(health_check))
+        .route("/metrics", get(metrics_handler))
+        .merge(api::router())
+        .merge(admin::router(state.clone()))
+        .merge(pages::router())
         .with_state(state);
 
     // Start the server
-    let addr = std::net::SocketAddr::from(([0, 0, 0, 0], port));
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
     println!("Starting webhook server on {}", addr);
 
     tokio::spawn(async move {
         match tokio::net::TcpListener::bind(addr).await {
             Ok(listener) => {
-                if let Err(e) = axum::serve(listener, app).await {
+                let service = app.into_make_service_with_connect_info::<SocketAddr>();
+                if let Err(e) = axum::serve(listener, service).await {
                     eprintln!("[ERROR] Server error: {}", e);
                 }
             }
@@ -198,3 +453,28 @@ pub async fn start_webhook_server(port: u16) -> (WebhookSender, WebhookReceiver)
 
     (webhook_sender, webhook_receiver)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_token() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(header_token(&headers), None);
+
+        headers.insert(TOKEN_HEADER, "secret".parse().unwrap());
+        assert_eq!(header_token(&headers), Some("secret"));
+
+        headers.insert(AUTHORIZATION, "Bearer other".parse().unwrap());
+        assert_eq!(header_token(&headers), Some("other"));
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+        assert!(!constant_time_eq(b"", b"secret"));
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+    routing::get,
+    Router,
+};
+
+use super::AppState;
+use crate::coc::database;
+use crate::{Data, Error};
+
+/// Seconds between automatic page reloads
+const REFRESH_SECS: u32 = 30;
+
+/// Public HTML pages following the event
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(ranking_page))
+        .route("/teams/{name}", get(team_page))
+        .route("/drops", get(drops_page))
+}
+
+/// Escapes text for use in HTML content and attributes
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Wraps page content in the shared layout, reloading every `REFRESH_SECS`
+fn layout(title: &str, body: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta http-equiv="refresh" content="{refresh}">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em auto; max-width: 60em; padding: 0 1em; background: #1e1f22; color: #dbdee1; }}
+a {{ color: #3498db; }}
+table {{ border-collapse: collapse; width: 100%; margin-bottom: 2em; }}
+th, td {{ text-align: left; padding: 0.4em 0.8em; border-bottom: 1px solid #3f4147; }}
+.accepted {{ color: #2ecc71; }}
+.rejected {{ color: #e74c3c; }}
+</style>
+</head>
+<body>
+<nav><a href="/">Ranking</a> · <a href="/drops">Recent drops</a></nav>
+<h1>{title}</h1>
+{body}
+</body>
+</html>
+"#,
+        refresh = REFRESH_SECS,
+        title = escape(title),
+        body = body
+    )
+}
+
+// Turns a page result into a response, hiding internal errors
+fn respond(result: Result<Option<String>, Error>) -> Response {
+    match result {
+        Ok(Some(page)) => Html(page).into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Html(layout("Not found", "<p>No team found with that name.</p>")),
+        )
+            .into_response(),
+        Err(e) => {
+            eprintln!("[ERROR] Failed to render page: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Html(layout("Error", "<p>Failed to read event data.</p>")),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Maximum level of the town hall, as in the ranking embed
+fn max_townhall_level(data: &Data) -> u32 {
+    let town_config = &data.town_config;
+    town_config
+        .assets
+        .get("townhall")
+        .or_else(|| town_config.assets.get("town_hall"))
+        .map(|config| config.max_level)
+        .unwrap_or(10)
+}
+
+async fn ranking_page(State(state): State<AppState>) -> Response {
+    let result = async {
+        let max_level = max_townhall_level(&state.data);
+        let teams = database::get_leaderboard(&state.data.database).await?;
+
+        let mut body = format!(
+            "<p>Town hall progression for every team. Maximum level: <b>{}</b></p>\n",
+            max_level
+        );
+        body.push_str("<table>\n<tr><th>Rank</th><th>Team</th><th>Town Hall</th><th>Progress</th><th>Resources</th></tr>\n");
+        for (index, (team_name, level, total_resources)) in teams.iter().enumerate() {
+            let percentage = (*level as f64 / max_level as f64 * 100.0).min(100.0);
+            let _ = writeln!(
+                body,
+                "<tr><td>{}</td><td><a href=\"/teams/{}\">{}</a></td><td>{}</td><td><progress max=\"100\" value=\"{:.0}\"></progress> {:.0}%</td><td>{}</td></tr>",
+                index + 1,
+                escape(team_name),
+                escape(team_name),
+                level,
+                percentage,
+                percentage,
+                total_resources
+            );
+        }
+        body.push_str("</table>\n");
+
+        Ok(Some(layout("Town Hall Ranking", &body)))
+    }
+    .await;
+
+    respond(result)
+}
+
+async fn team_page(State(state): State<AppState>, Path(name): Path<String>) -> Response {
+    let team_name = name.to_lowercase();
+    let result = async {
+        let pool = &state.data.database;
+        let team_id = match database::get_team_by_name(pool, &team_name).await? {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        let town_config = &state.data.town_config;
+
+        let mut bonuses: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (building, category, multiplier, flat_bonus) in
+            database::get_team_multipliers(pool, team_id).await?
+        {
+            // Skip entries with default multiplier and no bonus, as in the embed
+            if multiplier == 1.0 && flat_bonus == 0 {
+                continue;
+            }
+            bonuses
+                .entry(building)
+                .or_default()
+                .push(format!("{} ×{} +{}", category, multiplier, flat_bonus));
+        }
+
+        let mut body = String::from("<h2>Buildings</h2>\n<table>\n<tr><th>Building</th><th>Level</th><th>Bonuses</th></tr>\n");
+        for (building, level) in database::get_team_buildings(pool, team_id).await? {
+            let (display_name, max_level) = match town_config.assets.get(&building) {
+                Some(config) => (
+                    format!("{} {}", config.icon, config.name),
+                    config.max_level.to_string(),
+                ),
+                None => (building.clone(), "?".to_string()),
+            };
+            let bonus_text = bonuses
+                .get(&building)
+                .map(|bonuses| bonuses.join(", "))
+                .unwrap_or_default();
+            let _ = writeln!(
+                body,
+                "<tr><td>{}</td><td>{} / {}</td><td>{}</td></tr>",
+                escape(display_name.trim()),
+                level,
+                max_level,
+                escape(&bonus_text)
+            );
+        }
+        body.push_str("</table>\n");
+
+        body.push_str("<h2>Resources</h2>\n<table>\n<tr><th>Resource</th><th>Category</th><th>Quantity</th></tr>\n");
+        for (resource, category, quantity) in
+            database::get_team_resource_items(pool, team_id).await?
+        {
+            let _ = writeln!(
+                body,
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(&resource),
+                escape(&category),
+                quantity
+            );
+        }
+        body.push_str("</table>\n");
+
+        let members = database::get_team_members(pool, team_id).await?;
+        let _ = writeln!(
+            body,
+            "<p>Members: {}</p>",
+            escape(&members.join(", "))
+        );
+
+        Ok(Some(layout(&format!("Team {}", team_name), &body)))
+    }
+    .await;
+
+    respond(result)
+}
+
+async fn drops_page(State(state): State<AppState>) -> Response {
+    let events = state.data.drop_events.recent();
+
+    let mut body = String::new();
+    if events.is_empty() {
+        body.push_str("<p>No drops since the bot started.</p>\n");
+    } else {
+        body.push_str("<table>\n<tr><th>Time</th><th>Player</th><th>Team</th><th>Source</th><th>Items</th><th>Result</th></tr>\n");
+        for event in &events {
+            let items = event
+                .items
+                .iter()
+                .map(|item| {
+                    if event.accepted && item.credited > 0 {
+                        format!("{} x {} (+{})", item.quantity, item.name, item.credited)
+                    } else {
+                        format!("{} x {}", item.quantity, item.name)
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            let result = if event.accepted {
+                "<span class=\"accepted\">Credited</span>".to_string()
+            } else {
+                format!(
+                    "<span class=\"rejected\">{}</span>",
+                    escape(event.message.as_deref().unwrap_or("Rejected"))
+                )
+            };
+            let _ = writeln!(
+                body,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(&event.timestamp),
+                escape(&event.player),
+                escape(event.team.as_deref().unwrap_or("-")),
+                escape(&event.source),
+                escape(&items),
+                result
+            );
+        }
+        body.push_str("</table>\n");
+    }
+
+    Html(layout("Recent Drops", &body)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape("<b>\"Tom & Jerry's\"</b>"),
+            "&lt;b&gt;&quot;Tom &amp; Jerry&#39;s&quot;&lt;/b&gt;"
+        );
+    }
+}
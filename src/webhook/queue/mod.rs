@@ -0,0 +1,174 @@
+use std::time::Duration;
+
+use sqlx::SqlitePool;
+
+use crate::Error;
+
+/// Number of attempts before a failed payload is left for manual review
+pub const MAX_ATTEMPTS: i64 = 5;
+
+/// How often the worker sweeps the queue for failed payloads to retry
+pub const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Processing state of a queued webhook payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IngestStatus {
+    Pending,
+    Processing,
+    Processed,
+    Rejected,
+    Failed,
+}
+
+impl IngestStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IngestStatus::Pending => "pending",
+            IngestStatus::Processing => "processing",
+            IngestStatus::Processed => "processed",
+            IngestStatus::Rejected => "rejected",
+            IngestStatus::Failed => "failed",
+        }
+    }
+}
+
+/// Stores a raw payload and returns its queue ID
+pub async fn enqueue(pool: &SqlitePool, payload: &str) -> Result<i64, Error> {
+    let status = IngestStatus::Pending.as_str();
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO ingest_queue (payload, status)
+        VALUES ($1, $2)
+        RETURNING id as "id: i64"
+        "#,
+        payload,
+        status
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(result.id)
+}
+
+/// Reads a payload without claiming it
+pub async fn peek(pool: &SqlitePool, id: i64) -> Result<Option<String>, Error> {
+    let result = sqlx::query!(
+        r#"
+        SELECT payload
+        FROM ingest_queue
+        WHERE id = $1
+        "#,
+        id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(result.map(|record| record.payload))
+}
+
+/// Marks a pending or failed payload as processing and returns it, with the
+/// unix time it was received. Returns `None` if the entry was already claimed
+/// or finished.
+pub async fn claim(pool: &SqlitePool, id: i64) -> Result<Option<(String, Option<i64>)>, Error> {
+    let processing = IngestStatus::Processing.as_str();
+    let result = sqlx::query!(
+        r#"
+        UPDATE ingest_queue
+        SET status = $1, attempts = attempts + 1
+        WHERE id = $2 AND status IN ('pending', 'failed')
+        RETURNING payload, CAST(strftime('%s', received_at) AS INTEGER) as "received_at: i64"
+        "#,
+        processing,
+        id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(result.map(|record| (record.payload, record.received_at)))
+}
+
+/// Records the final status of a processing attempt
+pub async fn complete(
+    pool: &SqlitePool,
+    id: i64,
+    status: IngestStatus,
+    message: Option<&str>,
+) -> Result<(), Error> {
+    let status = status.as_str();
+    sqlx::query!(
+        r#"
+        UPDATE ingest_queue
+        SET status = $1, last_error = $2, processed_at = CURRENT_TIMESTAMP
+        WHERE id = $3
+        "#,
+        status,
+        message,
+        id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Returns every entry that still needs processing after a restart.
+/// Entries left in `processing` by a crash are moved back to `pending`.
+pub async fn resume(pool: &SqlitePool) -> Result<Vec<i64>, Error> {
+    sqlx::query!(
+        r#"
+        UPDATE ingest_queue
+        SET status = 'pending'
+        WHERE status = 'processing'
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    let records = sqlx::query!(
+        r#"
+        SELECT id as "id!: i64"
+        FROM ingest_queue
+        WHERE status = 'pending' OR (status = 'failed' AND attempts < $1)
+        ORDER BY id ASC
+        "#,
+        MAX_ATTEMPTS
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(records.into_iter().map(|record| record.id).collect())
+}
+
+/// Returns failed entries that are due for another attempt
+pub async fn retryable(pool: &SqlitePool) -> Result<Vec<i64>, Error> {
+    let records = sqlx::query!(
+        r#"
+        SELECT id as "id!: i64"
+        FROM ingest_queue
+        WHERE status = 'failed'
+            AND attempts < $1
+            AND processed_at <= datetime('now', '-30 seconds')
+        ORDER BY id ASC
+        "#,
+        MAX_ATTEMPTS
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(records.into_iter().map(|record| record.id).collect())
+}
+
+/// Counts entries that are waiting for or in the middle of processing
+pub async fn backlog(pool: &SqlitePool) -> Result<i64, Error> {
+    let result = sqlx::query!(
+        r#"
+        SELECT COUNT(*) as "count!: i64"
+        FROM ingest_queue
+        WHERE status IN ('pending', 'processing')
+        "#
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(result.count)
+}